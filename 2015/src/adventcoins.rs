@@ -1,55 +1,578 @@
+use std::collections::BTreeMap;
+use std::fmt::Display;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
 use std::thread;
+use std::time::{Duration, Instant};
 
-pub struct AdventCoins {
-    seed: String,
-    target: String,
+use crate::hasher::{Hasher, Md5};
+
+/// Bounds for the number of nonces a worker claims at once. Workers grow or
+/// shrink their chunks so each one takes roughly `CHUNK_TIME` to scan.
+const MIN_CHUNK: u64 = 1_000;
+const MAX_CHUNK: u64 = 1_000_000;
+const CHUNK_TIME: Duration = Duration::from_millis(20);
+
+/// Minimum time between two checkpoint writes.
+const CHECKPOINT_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Difficulty a digest has to meet, checked directly on the digest bytes.
+#[derive(Debug, PartialEq, Clone)]
+pub enum Target {
+    /// Digest starts with at least this many zero bits.
+    ZeroBits(usize),
+    /// Hex rendering of the digest starts with these nibbles.
+    HexPrefix(Vec<u8>),
+}
+
+impl Target {
+    /// Digest whose hex rendering starts with `n` zeros.
+    pub fn zeros(n: usize) -> Target {
+        Target::ZeroBits(4 * n)
+    }
+
+    pub fn matches(&self, digest: &[u8]) -> bool {
+        match self {
+            Target::ZeroBits(bits) => {
+                let (bytes, rest) = (bits / 8, bits % 8);
+
+                if bytes + (rest > 0) as usize > digest.len() {
+                    return false;
+                }
+
+                digest[..bytes].iter().all(|b| *b == 0)
+                    && (rest == 0 || digest[bytes] >> (8 - rest) == 0)
+            }
+            Target::HexPrefix(nibbles) => {
+                if nibbles.len() > 2 * digest.len() {
+                    return false;
+                }
+
+                nibbles.iter().enumerate().all(|(i, n)| {
+                    let byte = digest[i / 2];
+                    let nibble = if i % 2 == 0 { byte >> 4 } else { byte & 0xf };
+                    nibble == *n
+                })
+            }
+        }
+    }
+}
+
+impl Display for Target {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Target::ZeroBits(bits) => write!(f, "bits:{}", bits),
+            Target::HexPrefix(nibbles) => {
+                write!(f, "hex:")?;
+                for n in nibbles {
+                    write!(f, "{:x}", n)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub enum TargetError {
+    InvalidHex(char),
+}
+
+impl Display for TargetError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TargetError::InvalidHex(c) => write!(f, "invalid hex digit: {}", c),
+        }
+    }
+}
+
+impl FromStr for Target {
+    type Err = TargetError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let nibbles = s
+            .chars()
+            .map(|c| {
+                c.to_digit(16)
+                    .map(|n| n as u8)
+                    .ok_or(TargetError::InvalidHex(c))
+            })
+            .collect::<Result<Vec<u8>, TargetError>>()?;
+
+        Ok(Target::HexPrefix(nibbles))
+    }
+}
+
+/// Appends the decimal digits of `n` to `buf` without going through a
+/// `String`.
+fn push_decimal(buf: &mut Vec<u8>, mut n: u64) {
+    let mut digits = [0u8; 20];
+    let mut i = digits.len();
+
+    loop {
+        i -= 1;
+        digits[i] = b'0' + (n % 10) as u8;
+        n /= 10;
+        if n == 0 {
+            break;
+        }
+    }
+    buf.extend_from_slice(&digits[i..]);
+}
+
+/// Snapshot handed to the progress callback.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Progress {
+    /// Every nonce below this one has been hashed.
+    pub checked: u64,
+    /// Hashes per second since the run started.
+    pub rate: f64,
+}
+
+/// Keeps track of the finished chunks to work out how far the scan got
+/// without leaving holes behind.
+struct Tracker {
+    checked: u64,
+    pending: BTreeMap<u64, u64>,
+    resumed: u64,
+    started: Instant,
+    saved: Instant,
+    /// First checkpoint write that failed, which stops the scan.
+    error: Option<io::Error>,
+}
+
+impl Tracker {
+    fn new(checked: u64) -> Tracker {
+        let now = Instant::now();
+
+        Tracker {
+            checked,
+            pending: BTreeMap::new(),
+            resumed: checked,
+            started: now,
+            saved: now,
+            error: None,
+        }
+    }
+
+    /// Records `start..end` as scanned, returning whether the contiguous
+    /// range grew.
+    fn finish(&mut self, start: u64, end: u64) -> bool {
+        self.pending.insert(start, end);
+
+        let before = self.checked;
+        while let Some(end) = self.pending.remove(&self.checked) {
+            self.checked = end;
+        }
+        self.checked != before
+    }
+
+    fn progress(&self) -> Progress {
+        let elapsed = self.started.elapsed().as_secs_f64();
+        let hashed = (self.checked - self.resumed) as f64;
+
+        Progress {
+            checked: self.checked,
+            rate: if elapsed > 0.0 { hashed / elapsed } else { 0.0 },
+        }
+    }
+}
+
+pub struct AdventCoins<H: Hasher = Md5> {
+    hasher: H,
+    seed: Vec<u8>,
+    targets: Vec<Target>,
+    threads: usize,
+    progress: Option<Box<dyn Fn(Progress) + Send + Sync>>,
+    checkpoint: Option<PathBuf>,
 }
 
 impl AdventCoins {
-    pub fn new(seed: &str, target: &str) -> AdventCoins {
+    pub fn new(seed: &str, targets: &[Target]) -> AdventCoins {
+        AdventCoins::with_hasher(Md5, seed, targets)
+    }
+}
+
+impl<H: Hasher> AdventCoins<H> {
+    pub fn with_hasher(hasher: H, seed: &str, targets: &[Target]) -> AdventCoins<H> {
+        let threads = thread::available_parallelism().map_or(1, |n| n.get());
+
         AdventCoins {
-            seed: seed.to_string(),
-            target: target.to_string(),
+            hasher,
+            seed: seed.as_bytes().to_vec(),
+            targets: targets.to_vec(),
+            threads,
+            progress: None,
+            checkpoint: None,
         }
     }
 
-    pub fn mine(&self) -> u64 {
-        let mut lower: u64 = 0;
-        let offset: u64 = 10000;
+    pub fn set_threads(&mut self, threads: usize) {
+        self.threads = threads.max(1);
+    }
 
-        loop {
-            let mut threads = Vec::new();
+    /// Calls `f` every time the contiguous range of scanned nonces grows.
+    pub fn set_progress(&mut self, f: impl Fn(Progress) + Send + Sync + 'static) {
+        self.progress = Some(Box::new(f));
+    }
+
+    /// Saves the scan state to `path` as it goes and resumes from it if
+    /// it already holds a checkpoint for the same seed and targets.
+    pub fn set_checkpoint(&mut self, path: impl Into<PathBuf>) {
+        self.checkpoint = Some(path.into());
+    }
 
-            for _ in 0..10 {
-                let seed = self.seed.clone();
-                let target = self.target.clone();
+    fn header(&self) -> String {
+        let targets: Vec<String> = self.targets.iter().map(|t| t.to_string()).collect();
+
+        format!(
+            "seed {}\ntargets {}\n",
+            String::from_utf8_lossy(&self.seed),
+            targets.join(" ")
+        )
+    }
 
-                let handle =
-                    thread::spawn(move || AdventCoins::worker(seed, target, lower, lower + offset));
+    /// Reads a checkpoint, returning the first nonce left to scan and the
+    /// best hits found so far. Checkpoints for a different search are
+    /// ignored.
+    fn load(&self, path: &Path) -> io::Result<Option<(u64, Vec<u64>)>> {
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
 
-                threads.push(handle);
-                lower += offset;
+        let body = match content.strip_prefix(&self.header()) {
+            Some(body) => body,
+            None => return Ok(None),
+        };
+
+        let mut lines = body.lines();
+        let checked = lines
+            .next()
+            .and_then(|l| l.strip_prefix("checked "))
+            .and_then(|n| n.parse::<u64>().ok());
+        // Only `-` means no hit yet. Anything else unreadable could hide a
+        // hit below `checked` that resuming would never find again.
+        let hit = |n: &str| match n {
+            "-" => Some(u64::MAX),
+            n => n.parse::<u64>().ok(),
+        };
+        let best: Option<Vec<u64>> = lines
+            .next()
+            .and_then(|l| l.strip_prefix("best"))
+            .and_then(|l| l.split_whitespace().map(hit).collect());
+
+        match (checked, best) {
+            (Some(checked), Some(best)) if best.len() == self.targets.len() => {
+                Ok(Some((checked, best)))
             }
+            _ => Ok(None),
+        }
+    }
 
-            for result in threads {
-                let result = result.join().unwrap();
-                if let Some(r) = result {
-                    return r;
+    fn save(&self, path: &Path, checked: u64, best: &[AtomicU64]) -> io::Result<()> {
+        let best: Vec<String> = best
+            .iter()
+            .map(|b| match b.load(Ordering::Relaxed) {
+                u64::MAX => "-".to_string(),
+                b => b.to_string(),
+            })
+            .collect();
+        let content = format!(
+            "{}checked {}\nbest {}\n",
+            self.header(),
+            checked,
+            best.join(" ")
+        );
+
+        // Write then rename so an interrupted save never leaves a torn file.
+        let tmp = path.with_extension("tmp");
+        fs::write(&tmp, content)?;
+        fs::rename(tmp, path)
+    }
+
+    /// Records a scanned chunk, returning whether the scan should go on.
+    fn report(&self, tracker: &Mutex<Tracker>, start: u64, end: u64, best: &[AtomicU64]) -> bool {
+        let mut tracker = tracker.lock().unwrap();
+        if tracker.error.is_some() {
+            return false;
+        }
+        if !tracker.finish(start, end) {
+            return true;
+        }
+
+        if let Some(f) = &self.progress {
+            f(tracker.progress());
+        }
+
+        if let Some(path) = &self.checkpoint {
+            if tracker.saved.elapsed() >= CHECKPOINT_INTERVAL {
+                tracker.saved = Instant::now();
+                if let Err(e) = self.save(path, tracker.checked, best) {
+                    tracker.error = Some(e);
+                    return false;
                 }
             }
         }
+        true
+    }
+
+    /// Finds the smallest nonce matching each target, all in a single scan.
+    /// Targets without a match are reported as `u64::MAX`, which only
+    /// happens if the scan runs out of nonces.
+    ///
+    /// Workers pull chunks of nonces from a shared counter and publish hits
+    /// to a shared best index per target. Once a chunk starts past the best
+    /// hit of every target there is nothing smaller left to find, so workers
+    /// stop claiming new chunks and abandon their current one as soon as
+    /// they walk past it.
+    ///
+    /// Failing to read or write the checkpoint stops the scan with the I/O
+    /// error.
+    pub fn mine(&self) -> io::Result<Vec<u64>> {
+        let resume = match &self.checkpoint {
+            Some(path) => self.load(path)?,
+            None => None,
+        };
+        let (checked, best) = resume.unwrap_or_else(|| (0, vec![u64::MAX; self.targets.len()]));
+
+        let next = AtomicU64::new(checked);
+        let best: Vec<AtomicU64> = best.into_iter().map(AtomicU64::new).collect();
+        let tracker = Mutex::new(Tracker::new(checked));
+
+        thread::scope(|s| {
+            for _ in 0..self.threads {
+                s.spawn(|| self.worker(&next, &best, &tracker));
+            }
+        });
+
+        if let Some(e) = tracker.into_inner().unwrap().error {
+            return Err(e);
+        }
+
+        // Every best hit is final now, nothing up to the last one needs
+        // scanning again.
+        let limit = Self::limit(&best);
+        if let Some(path) = &self.checkpoint {
+            self.save(path, limit.saturating_add(1), &best)?;
+        }
+
+        Ok(best.into_iter().map(|b| b.into_inner()).collect())
     }
 
-    fn worker(seed: String, target: String, start: u64, stop: u64) -> Option<u64> {
-        for lower in start..stop {
-            let input: String = seed.clone() + &lower.to_string();
-            let hash = md5::compute(input);
-            let digest = format!("{:x}", hash);
+    fn limit(best: &[AtomicU64]) -> u64 {
+        best.iter()
+            .map(|b| b.load(Ordering::Relaxed))
+            .max()
+            .unwrap_or(0)
+    }
+
+    fn worker(&self, next: &AtomicU64, best: &[AtomicU64], tracker: &Mutex<Tracker>) {
+        let mut chunk = MIN_CHUNK;
+        let mut input = self.seed.clone();
+
+        loop {
+            let start = next.fetch_add(chunk, Ordering::Relaxed);
+            if start > Self::limit(best) {
+                return;
+            }
+
+            let now = Instant::now();
+            for nonce in start..start + chunk {
+                if nonce > Self::limit(best) {
+                    return;
+                }
+
+                input.truncate(self.seed.len());
+                push_decimal(&mut input, nonce);
+                let digest = self.hasher.digest(&input);
+
+                for (target, best) in self.targets.iter().zip(best) {
+                    if target.matches(digest.as_ref()) {
+                        best.fetch_min(nonce, Ordering::Relaxed);
+                    }
+                }
+            }
 
-            if digest.starts_with(&target) {
-                return Some(lower);
+            if !self.report(tracker, start, start + chunk, best) {
+                return;
             }
+
+            let elapsed = now.elapsed();
+            if elapsed < CHUNK_TIME / 2 {
+                chunk = (chunk * 2).min(MAX_CHUNK);
+            } else if elapsed > CHUNK_TIME * 2 {
+                chunk = (chunk / 2).max(MIN_CHUNK);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::hasher::{Sha256, Stretched};
+
+    fn check<H: Hasher>(hasher: &H, seed: &str, target: &Target, nonce: u64) -> bool {
+        let input = seed.to_string() + &nonce.to_string();
+        target.matches(hasher.digest(input.as_bytes()).as_ref())
+    }
+
+    #[test]
+    fn test_mine() {
+        let miner = AdventCoins::new("abcdef", &["00000".parse().unwrap()]);
+        assert_eq!(vec![609043], miner.mine().unwrap());
+    }
+
+    #[test]
+    fn test_smallest_nonce() {
+        let target = Target::zeros(3);
+        let mut miner = AdventCoins::new("pqrstuv", std::slice::from_ref(&target));
+        let expected = (0..).find(|n| check(&Md5, "pqrstuv", &target, *n)).unwrap();
+
+        for threads in [1, 3, 8] {
+            miner.set_threads(threads);
+            assert_eq!(vec![expected], miner.mine().unwrap());
+        }
+    }
+
+    #[test]
+    fn test_multiple_targets() {
+        let targets = [
+            Target::zeros(3),
+            Target::ZeroBits(10),
+            "abc".parse().unwrap(),
+            Target::zeros(2),
+        ];
+        let miner = AdventCoins::new("abcdef", &targets);
+
+        let expected: Vec<u64> = targets
+            .iter()
+            .map(|t| (0..).find(|n| check(&Md5, "abcdef", t, *n)).unwrap())
+            .collect();
+        assert_eq!(expected, miner.mine().unwrap());
+    }
+
+    #[test]
+    fn test_hashers() {
+        let target = Target::zeros(2);
+
+        let miner = AdventCoins::with_hasher(Sha256, "abcdef", std::slice::from_ref(&target));
+        let expected = (0..)
+            .find(|n| check(&Sha256, "abcdef", &target, *n))
+            .unwrap();
+        assert_eq!(vec![expected], miner.mine().unwrap());
+
+        let stretched = Stretched::new(Md5, 5);
+        let expected = (0..)
+            .find(|n| check(&stretched, "abc", &target, *n))
+            .unwrap();
+        let miner = AdventCoins::with_hasher(stretched, "abc", &[target]);
+        assert_eq!(vec![expected], miner.mine().unwrap());
+    }
+
+    #[test]
+    fn test_progress() {
+        let seen = std::sync::Arc::new(Mutex::new(Vec::new()));
+        let mut miner = AdventCoins::new("abcdef", &[Target::zeros(4)]);
+        miner.set_threads(4);
+
+        let log = seen.clone();
+        miner.set_progress(move |p| log.lock().unwrap().push(p.checked));
+        let result = miner.mine().unwrap();
+
+        let seen = seen.lock().unwrap();
+        assert!(!seen.is_empty());
+        assert!(seen.windows(2).all(|w| w[0] < w[1]));
+        assert!(*seen.last().unwrap() <= result[0] + MAX_CHUNK);
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let path = std::env::temp_dir().join(format!("adventcoins-{}.txt", std::process::id()));
+        let targets = [Target::zeros(5), Target::zeros(3)];
+
+        // A made up checkpoint: the scan picks up at 600000 and trusts the
+        // hit already recorded for the second target.
+        fs::write(
+            &path,
+            "seed abcdef\ntargets bits:20 bits:12\nchecked 600000\nbest - 3337\n",
+        )
+        .unwrap();
+
+        let first = std::sync::Arc::new(Mutex::new(None));
+        let log = first.clone();
+        let mut miner = AdventCoins::new("abcdef", &targets);
+        miner.set_checkpoint(&path);
+        miner.set_progress(move |p| {
+            log.lock().unwrap().get_or_insert(p.checked);
+        });
+
+        assert_eq!(vec![609043, 3337], miner.mine().unwrap());
+        assert!(first.lock().unwrap().unwrap() > 600000);
+
+        let saved = fs::read_to_string(&path).unwrap();
+        assert_eq!(
+            "seed abcdef\ntargets bits:20 bits:12\nchecked 609044\nbest 609043 3337\n",
+            saved
+        );
+
+        // A hit that can't be read discards the whole checkpoint.
+        fs::write(
+            &path,
+            "seed abcdef\ntargets bits:20 bits:12\nchecked 600000\nbest - 33x7\n",
+        )
+        .unwrap();
+        assert_eq!(None, miner.load(&path).unwrap());
+
+        // Checkpoints for another search are ignored.
+        let mut miner = AdventCoins::new("pqrstuv", &targets);
+        miner.set_checkpoint(&path);
+        assert_eq!(None, miner.load(&path).unwrap());
+
+        fs::remove_file(&path).unwrap();
+    }
+
+    #[test]
+    fn test_checkpoint_errors() {
+        let dir = std::env::temp_dir();
+
+        // Unreadable, since it's a directory.
+        let mut miner = AdventCoins::new("abcdef", &[Target::zeros(3)]);
+        miner.set_checkpoint(&dir);
+        assert!(miner.mine().is_err());
+
+        // Unwritable, since its directory doesn't exist.
+        let missing = dir.join(format!("adventcoins-{}", std::process::id()));
+        miner.set_checkpoint(missing.join("checkpoint.txt"));
+        assert_eq!(io::ErrorKind::NotFound, miner.mine().unwrap_err().kind());
+    }
+
+    #[test]
+    fn test_target() {
+        let digest = [0x00, 0x0f, 0xab];
+
+        assert!(Target::ZeroBits(12).matches(&digest));
+        assert!(!Target::ZeroBits(13).matches(&digest));
+        assert!(!Target::ZeroBits(25).matches(&digest));
+        assert!(Target::zeros(3).matches(&digest));
+        assert!("000fa".parse::<Target>().unwrap().matches(&digest));
+        assert!(!"000fb".parse::<Target>().unwrap().matches(&digest));
+        assert!(!"000fab0".parse::<Target>().unwrap().matches(&digest));
+        assert_eq!(Err(TargetError::InvalidHex('g')), "00g".parse::<Target>());
+    }
+
+    #[test]
+    fn test_push_decimal() {
+        for n in [0, 7, 10, 609043, u64::MAX] {
+            let mut buf = b"seed".to_vec();
+            push_decimal(&mut buf, n);
+            assert_eq!(format!("seed{}", n).as_bytes(), &buf[..]);
         }
-        None
     }
 }
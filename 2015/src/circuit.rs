@@ -1,148 +1,353 @@
-use std::collections::{HashMap, VecDeque};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::fmt::Display;
 
-#[derive(Debug)]
+pub mod dot;
+pub mod optimize;
+
+/// Gates driving each wire, wires forced to a constant, and the signals
+/// worked out so far.
+#[derive(Debug, Default)]
 pub struct Circuit {
+    gates: HashMap<String, Gate>,
+    overrides: HashMap<String, u16>,
     wires: HashMap<String, u16>,
 }
 
 enum CircuitError {
     InvalidOperation(String),
-    UnkownInput,
+}
+
+/// Gate input, either another wire or a fixed signal.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Operand {
+    Wire(String),
+    Signal(u16),
+}
+
+impl Operand {
+    fn parse(input: &str) -> Operand {
+        match input.parse::<u16>() {
+            Ok(signal) => Operand::Signal(signal),
+            Err(_) => Operand::Wire(input.to_string()),
+        }
+    }
+
+    fn value(&self, wires: &HashMap<String, u16>) -> Option<u16> {
+        match self {
+            Operand::Wire(wire) => wires.get(wire).copied(),
+            Operand::Signal(signal) => Some(*signal),
+        }
+    }
+}
+
+impl Display for Operand {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Operand::Wire(wire) => write!(f, "{}", wire),
+            Operand::Signal(signal) => write!(f, "{}", signal),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum Gate {
+    Assignment(Operand),
+    Not(Operand),
+    And(Operand, Operand),
+    Or(Operand, Operand),
+    LShift(Operand, Operand),
+    RShift(Operand, Operand),
+}
+
+impl Gate {
+    pub fn operands(&self) -> Vec<&Operand> {
+        match self {
+            Gate::Assignment(x) | Gate::Not(x) => vec![x],
+            Gate::And(x, y) | Gate::Or(x, y) | Gate::LShift(x, y) | Gate::RShift(x, y) => {
+                vec![x, y]
+            }
+        }
+    }
+
+    fn operands_mut(&mut self) -> Vec<&mut Operand> {
+        match self {
+            Gate::Assignment(x) | Gate::Not(x) => vec![x],
+            Gate::And(x, y) | Gate::Or(x, y) | Gate::LShift(x, y) | Gate::RShift(x, y) => {
+                vec![x, y]
+            }
+        }
+    }
+
+    pub fn opcode(&self) -> &'static str {
+        match self {
+            Gate::Assignment(_) => "ASSIGN",
+            Gate::Not(_) => "NOT",
+            Gate::And(..) => "AND",
+            Gate::Or(..) => "OR",
+            Gate::LShift(..) => "LSHIFT",
+            Gate::RShift(..) => "RSHIFT",
+        }
+    }
+
+    /// Wires feeding the gate, once per use.
+    pub fn inputs(&self) -> impl Iterator<Item = &String> {
+        self.operands().into_iter().filter_map(|op| match op {
+            Operand::Wire(wire) => Some(wire),
+            Operand::Signal(_) => None,
+        })
+    }
+
+    /// Output signal, or `None` while an input wire has no signal yet.
+    fn eval(&self, wires: &HashMap<String, u16>) -> Option<u16> {
+        let signal = match self {
+            Gate::Assignment(x) => x.value(wires)?,
+            Gate::Not(x) => !x.value(wires)?,
+            Gate::And(x, y) => x.value(wires)? & y.value(wires)?,
+            Gate::Or(x, y) => x.value(wires)? | y.value(wires)?,
+            Gate::LShift(x, y) => x.value(wires)? << y.value(wires)?,
+            Gate::RShift(x, y) => x.value(wires)? >> y.value(wires)?,
+        };
+        Some(signal)
+    }
+}
+
+/// Left hand side of an instruction, as written in the puzzle.
+impl Display for Gate {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Gate::Assignment(x) => write!(f, "{}", x),
+            Gate::Not(x) => write!(f, "NOT {}", x),
+            Gate::And(x, y) | Gate::Or(x, y) | Gate::LShift(x, y) | Gate::RShift(x, y) => {
+                write!(f, "{} {} {}", x, self.opcode(), y)
+            }
+        }
+    }
 }
 
 impl Circuit {
     pub fn new() -> Circuit {
+        let gates = HashMap::new();
+        let overrides = HashMap::new();
         let wires = HashMap::new();
-        Circuit { wires }
-    }
-
-    pub fn reset(&mut self) {
-        self.wires.clear();
+        Circuit {
+            gates,
+            overrides,
+            wires,
+        }
     }
 
+    /// Forces `wire` to `signal` whatever drives it, until cleared. Signals
+    /// depending on it are worked out again when next asked for.
     pub fn set_wire(&mut self, wire: String, signal: u16) {
+        self.invalidate(&[wire.as_str()]);
+        self.overrides.insert(wire.clone(), signal);
         self.wires.insert(wire, signal);
     }
 
-    pub fn get_wire(&self, r: String) -> u16 {
-        self.wires[&r]
+    /// Hands `wire` back to the gate driving it.
+    pub fn clear_wire(&mut self, wire: &str) {
+        if self.overrides.remove(wire).is_some() {
+            self.invalidate(&[wire]);
+        }
     }
 
-    pub fn assemble(&mut self, input: Vec<&str>) {
-        let mut q = VecDeque::from_iter(input);
-        let mut start_elements = q.len();
-        let mut counter = start_elements;
-
-        while !q.is_empty() {
-            let i = q.pop_front().unwrap();
-            let op = self.decode(i.to_string());
-
-            match op {
-                Err(e) => match e {
-                    CircuitError::InvalidOperation(e) => panic!("Invalid operation: {}", e),
-                    CircuitError::UnkownInput => {
-                        q.push_back(i);
-                        continue;
-                    }
-                },
-                Ok(op) => {
-                    match op {
-                        Operation::Assignment(reg, value) => {
-                            if self.wires.get(&reg).is_some() {
-                                continue;
-                            }
-                            self.wires.insert(reg, value)
-                        }
-                        Operation::Not(reg, value) => self.wires.insert(reg, !value),
-                        Operation::And(reg, x, y) => self.wires.insert(reg, x & y),
-                        Operation::Or(reg, x, y) => self.wires.insert(reg, x | y),
-                        Operation::LShift(reg, x, y) => self.wires.insert(reg, x << y),
-                        Operation::RShift(reg, x, y) => self.wires.insert(reg, x >> y),
-                    };
+    /// Forgets the signal on `changed` wires and on every wire they feed.
+    fn invalidate(&mut self, changed: &[&str]) {
+        if self.wires.is_empty() {
+            return;
+        }
+
+        let mut dependents: HashMap<&str, Vec<&str>> = HashMap::new();
+        for (target, gate) in &self.gates {
+            for input in gate.inputs() {
+                dependents.entry(input).or_default().push(target);
+            }
+        }
+
+        let mut stack = changed.to_vec();
+        let mut seen = HashSet::new();
+        while let Some(w) = stack.pop() {
+            if !seen.insert(w) || self.wires.remove(w).is_none() {
+                continue;
+            }
+            stack.extend(dependents.get(w).into_iter().flatten());
+        }
+
+        for (w, signal) in &self.overrides {
+            self.wires.insert(w.clone(), *signal);
+        }
+    }
+
+    /// Signal on wire `r`, only evaluating the gates it depends on.
+    pub fn get_wire(&mut self, r: String) -> u16 {
+        match self.resolve(&r) {
+            Some(signal) => signal,
+            None => panic!("Wire {} can't be resolved", r),
+        }
+    }
+
+    /// Stores the gates without evaluating anything.
+    pub fn load(&mut self, input: Vec<&str>) {
+        let mut targets = Vec::new();
+
+        for i in input {
+            match Circuit::decode(i) {
+                Err(CircuitError::InvalidOperation(e)) => panic!("Invalid operation: {}", e),
+                Ok((target, gate)) => {
+                    self.gates.insert(target.clone(), gate);
+                    targets.push(target);
                 }
             }
+        }
+
+        let targets: Vec<&str> = targets.iter().map(|t| t.as_str()).collect();
+        self.invalidate(&targets);
+    }
+
+    /// Every gate in the puzzle's syntax, one per line and sorted by the wire
+    /// they drive. Overridden wires show up as plain assignments.
+    pub fn netlist(&self) -> String {
+        let mut lines: Vec<(&String, String)> = self
+            .gates
+            .iter()
+            .filter(|(target, _)| !self.overrides.contains_key(*target))
+            .map(|(target, gate)| (target, gate.to_string()))
+            .chain(
+                self.overrides
+                    .iter()
+                    .map(|(target, signal)| (target, signal.to_string())),
+            )
+            .collect();
+        lines.sort();
+
+        lines
+            .into_iter()
+            .map(|(target, gate)| format!("{} -> {}\n", gate, target))
+            .collect()
+    }
 
-            // Check we are actually removing entries
-            counter -= 1;
-            if counter == 0 {
-                if start_elements == q.len() {
-                    panic!("Elements are no longer being processed: {:?}", q);
+    /// Depth first from `wire`, keeping the pending wires on an explicit
+    /// stack so deep netlists can't overflow the call stack. Every signal
+    /// found on the way is kept. `None` if some wire has no gate driving it
+    /// or feeds itself.
+    fn resolve(&mut self, wire: &str) -> Option<u16> {
+        let mut stack = vec![wire.to_string()];
+        let mut visiting = HashSet::new();
+
+        while let Some(w) = stack.last() {
+            if self.wires.contains_key(w) {
+                stack.pop();
+                continue;
+            }
+
+            let gate = self.gates.get(w)?;
+            let missing: Vec<String> = gate
+                .inputs()
+                .filter(|i| !self.wires.contains_key(*i))
+                .cloned()
+                .collect();
+
+            if missing.is_empty() {
+                let signal = gate.eval(&self.wires).unwrap();
+                let w = stack.pop().unwrap();
+                visiting.remove(&w);
+                self.wires.insert(w, signal);
+            } else {
+                // Back on top with inputs still missing: a loop.
+                if !visiting.insert(w.clone()) {
+                    return None;
                 }
-                start_elements = q.len();
-                counter = start_elements;
+                stack.extend(missing);
             }
         }
+        self.wires.get(wire).copied()
     }
 
-    fn parse_arg(&self, input: &str) -> Option<u16> {
-        let signal = input.parse::<u16>();
+    /// Evaluates every gate once, each as soon as all of its inputs are known
+    /// (Kahn's algorithm).
+    fn evaluate(&mut self) {
+        let gates: Vec<(&String, &Gate)> = self
+            .gates
+            .iter()
+            .filter(|(target, _)| !self.overrides.contains_key(*target))
+            .collect();
+
+        // Gates waiting on each wire, and how many inputs each still lacks.
+        let mut dependents: HashMap<&str, Vec<usize>> = HashMap::new();
+        let mut pending = vec![0; gates.len()];
+
+        for (i, (_, gate)) in gates.iter().enumerate() {
+            for wire in gate.inputs() {
+                if !self.wires.contains_key(wire) {
+                    dependents.entry(wire).or_default().push(i);
+                    pending[i] += 1;
+                }
+            }
+        }
+
+        let mut q: VecDeque<usize> = (0..gates.len()).filter(|i| pending[*i] == 0).collect();
+        let mut evaluated = 0;
+
+        while let Some(i) = q.pop_front() {
+            let (target, gate) = gates[i];
+            let signal = gate.eval(&self.wires).unwrap();
+            evaluated += 1;
+
+            if self.wires.insert(target.clone(), signal).is_some() {
+                continue;
+            }
 
-        match signal {
-            Ok(signal) => Some(signal),
-            Err(_) => self.wires.get(input).copied(),
+            for d in dependents.remove(target.as_str()).unwrap_or_default() {
+                pending[d] -= 1;
+                if pending[d] == 0 {
+                    q.push_back(d);
+                }
+            }
         }
+
+        if evaluated < gates.len() {
+            let stuck: Vec<&String> = (0..gates.len())
+                .filter(|i| pending[*i] > 0)
+                .map(|i| gates[i].0)
+                .collect();
+            panic!("Elements are no longer being processed: {:?}", stuck);
+        }
+    }
+
+    /// Loads the gates and evaluates every wire up front.
+    pub fn assemble(&mut self, input: Vec<&str>) {
+        self.load(input);
+        self.evaluate();
     }
 
-    fn decode(&self, op: String) -> Result<Operation, CircuitError> {
+    fn decode(op: &str) -> Result<(String, Gate), CircuitError> {
         let op: Vec<&str> = op.split(' ').collect();
 
         match &op[..] {
             [x, opcode, y, "->", target] => {
-                let x = self.parse_arg(x);
-                let y = self.parse_arg(y);
-                let target = target.to_string();
+                let x = Operand::parse(x);
+                let y = Operand::parse(y);
 
-                if x == None || y == None {
-                    return Err(CircuitError::UnkownInput);
-                }
-
-                let x = x.unwrap();
-                let y = y.unwrap();
-
-                if opcode == &"AND" {
-                    Ok(Operation::And(target, x, y))
-                } else if opcode == &"OR" {
-                    Ok(Operation::Or(target, x, y))
-                } else if opcode == &"LSHIFT" {
-                    Ok(Operation::LShift(target, x, y))
-                } else if opcode == &"RSHIFT" {
-                    Ok(Operation::RShift(target, x, y))
-                } else {
-                    Err(CircuitError::InvalidOperation(op.join(" ")))
-                }
+                let gate = match *opcode {
+                    "AND" => Gate::And(x, y),
+                    "OR" => Gate::Or(x, y),
+                    "LSHIFT" => Gate::LShift(x, y),
+                    "RSHIFT" => Gate::RShift(x, y),
+                    _ => return Err(CircuitError::InvalidOperation(op.join(" "))),
+                };
+                Ok((target.to_string(), gate))
             }
             ["NOT", value, "->", target] => {
-                let value = self.parse_arg(value);
-
-                if let Some(value) = value {
-                    Ok(Operation::Not(target.to_string(), value))
-                } else {
-                    Err(CircuitError::UnkownInput)
-                }
+                Ok((target.to_string(), Gate::Not(Operand::parse(value))))
             }
             [value, "->", target] => {
-                let value = self.parse_arg(value);
-
-                if let Some(value) = value {
-                    Ok(Operation::Assignment(target.to_string(), value))
-                } else {
-                    Err(CircuitError::UnkownInput)
-                }
+                Ok((target.to_string(), Gate::Assignment(Operand::parse(value))))
             }
             _ => Err(CircuitError::InvalidOperation(op.join(" "))),
         }
     }
 }
 
-pub enum Operation {
-    Assignment(String, u16),
-    Not(String, u16),
-    And(String, u16, u16),
-    Or(String, u16, u16),
-    LShift(String, u16, u16),
-    RShift(String, u16, u16),
-}
-
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -172,4 +377,76 @@ mod tests {
         assert_eq!(circuit.wires["x"], 123);
         assert_eq!(circuit.wires["y"], 456);
     }
+
+    #[test]
+    fn test_override() {
+        let mut circuit = Circuit::new();
+        let input = ["x -> y", "y AND 7 -> z", "12 -> x", "NOT y -> w"];
+
+        circuit.set_wire("x".to_string(), 5);
+        circuit.assemble(input.to_vec());
+
+        assert_eq!(5, circuit.get_wire("y".to_string()));
+        assert_eq!(5, circuit.get_wire("z".to_string()));
+        assert_eq!(!5, circuit.get_wire("w".to_string()));
+
+        // No need to load the gates again.
+        circuit.set_wire("y".to_string(), 3);
+        assert_eq!(3, circuit.get_wire("z".to_string()));
+        assert_eq!(!3, circuit.get_wire("w".to_string()));
+        assert_eq!(5, circuit.get_wire("x".to_string()));
+
+        circuit.clear_wire("x");
+        circuit.clear_wire("y");
+        assert_eq!(4, circuit.get_wire("z".to_string()));
+        assert_eq!(12, circuit.get_wire("y".to_string()));
+    }
+
+    #[test]
+    fn test_lazy() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            "x AND y -> d",
+            "x OR y -> e",
+            "123 -> x",
+            "456 -> y",
+            "NOT x -> h",
+        ]);
+
+        assert_eq!(72, circuit.get_wire("d".to_string()));
+        let mut evaluated: Vec<&String> = circuit.wires.keys().collect();
+        evaluated.sort();
+        assert_eq!(vec!["d", "x", "y"], evaluated);
+
+        assert_eq!(65412, circuit.get_wire("h".to_string()));
+        assert_eq!(4, circuit.wires.len());
+    }
+
+    #[test]
+    fn test_deep() {
+        let n = 100_000;
+        let input: Vec<String> = (0..n)
+            .map(|i| format!("w{} AND 65535 -> w{}", i + 1, i))
+            .chain([format!("7 -> w{}", n)])
+            .collect();
+
+        let mut circuit = Circuit::new();
+        circuit.load(input.iter().map(|s| s.as_str()).collect());
+        assert_eq!(7, circuit.get_wire("w0".to_string()));
+    }
+
+    #[test]
+    #[should_panic(expected = "can't be resolved")]
+    fn test_loop() {
+        let mut circuit = Circuit::new();
+        circuit.load(vec!["a -> b", "b OR 1 -> c", "c -> a"]);
+        circuit.get_wire("a".to_string());
+    }
+
+    #[test]
+    #[should_panic(expected = "no longer being processed")]
+    fn test_unresolved() {
+        let mut circuit = Circuit::new();
+        circuit.assemble(vec!["a -> b", "b OR c -> a", "1 -> d"]);
+    }
 }
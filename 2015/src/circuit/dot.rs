@@ -0,0 +1,173 @@
+use std::collections::{BTreeSet, HashSet};
+use std::fmt::Write;
+
+use super::{Circuit, Operand};
+
+/// What `Circuit::to_dot` draws.
+#[derive(Debug, Default)]
+pub struct DotOptions {
+    /// Only draw the wires this one depends on, itself included.
+    pub cone: Option<String>,
+    /// Wires drawn in red, along with the gates leading from each one to the
+    /// next.
+    pub highlight: Vec<String>,
+}
+
+/// Attributes marking highlighted nodes and edges.
+const RED_NODE: &str = ", color=red";
+const RED_EDGE: &str = " [color=red]";
+
+impl Circuit {
+    /// Every wire `root` depends on, stopping at overridden wires since
+    /// their gates are out of the picture.
+    fn fan_in<'a>(&'a self, root: &'a str) -> BTreeSet<&'a str> {
+        let mut cone = BTreeSet::new();
+        let mut stack = vec![root];
+
+        while let Some(w) = stack.pop() {
+            if !cone.insert(w) || self.overrides.contains_key(w) {
+                continue;
+            }
+            if let Some(gate) = self.gates.get(w) {
+                stack.extend(gate.inputs().map(|i| i.as_str()));
+            }
+        }
+        cone
+    }
+
+    /// Graphviz description of the circuit. Wires are ellipses showing their
+    /// signal once it's known, gates are boxes named after their operation
+    /// and constants are plain text.
+    pub fn to_dot(&self, options: &DotOptions) -> String {
+        let wires: BTreeSet<&str> = match &options.cone {
+            Some(root) => self.fan_in(root),
+            None => self
+                .gates
+                .iter()
+                .flat_map(|(target, gate)| gate.inputs().chain([target]))
+                .chain(self.overrides.keys())
+                .map(|w| w.as_str())
+                .collect(),
+        };
+
+        let highlighted: HashSet<&str> = options.highlight.iter().map(|w| w.as_str()).collect();
+        let steps: HashSet<(&str, &str)> = options
+            .highlight
+            .windows(2)
+            .map(|w| (w[0].as_str(), w[1].as_str()))
+            .collect();
+        let node = |on: bool| if on { RED_NODE } else { "" };
+        let edge = |on: bool| if on { RED_EDGE } else { "" };
+
+        let mut out = String::from("digraph circuit {\n");
+
+        for w in &wires {
+            let label = match (self.wires.get(*w), self.overrides.contains_key(*w)) {
+                (Some(signal), true) => format!("{}\\n{} (override)", w, signal),
+                (Some(signal), false) => format!("{}\\n{}", w, signal),
+                (None, _) => w.to_string(),
+            };
+            let on = highlighted.contains(w);
+            writeln!(out, "    \"{}\" [label=\"{}\"{}];", w, label, node(on)).unwrap();
+        }
+
+        for w in &wires {
+            if self.overrides.contains_key(*w) {
+                continue;
+            }
+            let Some(gate) = self.gates.get(*w) else {
+                continue;
+            };
+
+            let id = format!("{}:gate", w);
+            let on = gate.inputs().any(|i| steps.contains(&(i.as_str(), *w)));
+            writeln!(
+                out,
+                "    \"{}\" [label=\"{}\", shape=box{}];",
+                id,
+                gate.opcode(),
+                node(on)
+            )
+            .unwrap();
+
+            for (i, op) in gate.operands().into_iter().enumerate() {
+                match op {
+                    Operand::Wire(input) => {
+                        let on = steps.contains(&(input.as_str(), *w));
+                        writeln!(out, "    \"{}\" -> \"{}\"{};", input, id, edge(on)).unwrap();
+                    }
+                    Operand::Signal(signal) => {
+                        let constant = format!("{}:{}", w, i);
+                        writeln!(
+                            out,
+                            "    \"{}\" [label=\"{}\", shape=plaintext];",
+                            constant, signal
+                        )
+                        .unwrap();
+                        writeln!(out, "    \"{}\" -> \"{}\";", constant, id).unwrap();
+                    }
+                }
+            }
+            writeln!(out, "    \"{}\" -> \"{}\"{};", id, w, edge(on)).unwrap();
+        }
+
+        out.push_str("}\n");
+        out
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn circuit() -> Circuit {
+        let mut circuit = Circuit::new();
+        circuit.load(vec![
+            "x AND y -> d",
+            "d LSHIFT 2 -> f",
+            "123 -> x",
+            "456 -> y",
+            "NOT x -> h",
+        ]);
+        circuit
+    }
+
+    #[test]
+    fn test_dot() {
+        let mut circuit = circuit();
+        circuit.get_wire("d".to_string());
+        let dot = circuit.to_dot(&DotOptions::default());
+
+        assert!(dot.starts_with("digraph circuit {\n"));
+        assert!(dot.ends_with("}\n"));
+        assert!(dot.contains("    \"d\" [label=\"d\\n72\"];\n"));
+        assert!(dot.contains("    \"f\" [label=\"f\"];\n"));
+        assert!(dot.contains("    \"f:gate\" [label=\"LSHIFT\", shape=box];\n"));
+        assert!(dot.contains("    \"f:1\" [label=\"2\", shape=plaintext];\n"));
+        assert!(dot.contains("    \"f:1\" -> \"f:gate\";\n"));
+        assert!(dot.contains("    \"d\" -> \"f:gate\";\n"));
+        assert!(dot.contains("    \"f:gate\" -> \"f\";\n"));
+        assert_eq!(5, dot.matches(":gate\" [label").count());
+    }
+
+    #[test]
+    fn test_cone() {
+        let mut circuit = circuit();
+        circuit.set_wire("y".to_string(), 7);
+
+        let dot = circuit.to_dot(&DotOptions {
+            cone: Some("f".to_string()),
+            highlight: vec!["x".to_string(), "d".to_string(), "f".to_string()],
+        });
+
+        assert!(!dot.contains("\"h\""));
+        assert!(!dot.contains("\"y:gate\""));
+        assert!(dot.contains("    \"y\" [label=\"y\\n7 (override)\"];\n"));
+        assert!(dot.contains("    \"x\" [label=\"x\", color=red];\n"));
+        assert!(dot.contains("    \"x\" -> \"d:gate\" [color=red];\n"));
+        assert!(dot.contains("    \"y\" -> \"d:gate\";\n"));
+        assert!(dot.contains("    \"d:gate\" [label=\"AND\", shape=box, color=red];\n"));
+        assert!(dot.contains("    \"d:gate\" -> \"d\" [color=red];\n"));
+        assert!(dot.contains("    \"f:gate\" -> \"f\" [color=red];\n"));
+    }
+}
@@ -0,0 +1,228 @@
+use std::collections::{HashMap, HashSet};
+
+use super::{Circuit, Gate, Operand};
+
+type Gates = HashMap<String, Gate>;
+
+/// What each plain assignment boils down to once chains of them are
+/// followed: a constant, or the first wire driven by something else.
+fn substitutions(gates: &Gates) -> HashMap<String, Operand> {
+    let mut subs = HashMap::new();
+
+    for (w, gate) in gates {
+        let Gate::Assignment(op) = gate else {
+            continue;
+        };
+
+        let mut op = op.clone();
+        let mut seen = HashSet::from([w.as_str()]);
+        while let Operand::Wire(u) = &op {
+            match gates.get_key_value(u) {
+                Some((u, Gate::Assignment(next))) if seen.insert(u.as_str()) => op = next.clone(),
+                _ => break,
+            }
+        }
+
+        if op != Operand::Wire(w.clone()) {
+            subs.insert(w.clone(), op);
+        }
+    }
+    subs
+}
+
+/// Reads constants and copies straight from their source.
+fn propagate(gates: &mut Gates) -> bool {
+    let subs = substitutions(gates);
+    let mut changed = false;
+
+    for gate in gates.values_mut() {
+        for op in gate.operands_mut() {
+            if let Operand::Wire(w) = op {
+                if let Some(sub) = subs.get(w) {
+                    if sub != op {
+                        *op = sub.clone();
+                        changed = true;
+                    }
+                }
+            }
+        }
+    }
+    changed
+}
+
+/// Cheaper gate computing the same signal, if there is one.
+fn simplified(gate: &Gate, gates: &Gates) -> Option<Gate> {
+    use Operand::Signal;
+
+    let constant = gate.operands().iter().all(|op| matches!(op, Signal(_)));
+    let overflows =
+        matches!(gate, Gate::LShift(_, Signal(n)) | Gate::RShift(_, Signal(n)) if *n >= 16);
+
+    if constant && !overflows && !matches!(gate, Gate::Assignment(_)) {
+        return Some(Gate::Assignment(Signal(gate.eval(&HashMap::new())?)));
+    }
+
+    let simpler = match gate {
+        Gate::And(x, Signal(u16::MAX)) | Gate::And(Signal(u16::MAX), x) => {
+            Gate::Assignment(x.clone())
+        }
+        Gate::And(_, Signal(0)) | Gate::And(Signal(0), _) => Gate::Assignment(Signal(0)),
+        Gate::Or(x, Signal(0)) | Gate::Or(Signal(0), x) => Gate::Assignment(x.clone()),
+        Gate::Or(_, Signal(u16::MAX)) | Gate::Or(Signal(u16::MAX), _) => {
+            Gate::Assignment(Signal(u16::MAX))
+        }
+        Gate::LShift(x, Signal(0)) | Gate::RShift(x, Signal(0)) => Gate::Assignment(x.clone()),
+        Gate::And(x, y) | Gate::Or(x, y) if x == y => Gate::Assignment(x.clone()),
+        Gate::Not(Operand::Wire(w)) => match gates.get(w) {
+            Some(Gate::Not(x)) => Gate::Assignment(x.clone()),
+            _ => return None,
+        },
+        _ => return None,
+    };
+    Some(simpler)
+}
+
+fn simplify(gates: &mut Gates) -> bool {
+    let changes: Vec<(String, Gate)> = gates
+        .iter()
+        .filter_map(|(w, gate)| Some((w.clone(), simplified(gate, gates)?)))
+        .collect();
+
+    let changed = !changes.is_empty();
+    gates.extend(changes);
+    changed
+}
+
+/// Turns gates computing the same thing as an earlier one (by wire name)
+/// into copies of it.
+fn merge(gates: &mut Gates) -> bool {
+    let mut names: Vec<String> = gates.keys().cloned().collect();
+    names.sort();
+
+    let mut first: HashMap<Gate, String> = HashMap::new();
+    let mut changed = false;
+
+    for w in names {
+        // Both orders of AND and OR compute the same thing.
+        let key = match gates[&w].clone() {
+            Gate::Assignment(_) => continue,
+            Gate::And(x, y) if x > y => Gate::And(y, x),
+            Gate::Or(x, y) if x > y => Gate::Or(y, x),
+            gate => gate,
+        };
+
+        match first.get(&key) {
+            Some(original) => {
+                gates.insert(w, Gate::Assignment(Operand::Wire(original.clone())));
+                changed = true;
+            }
+            None => {
+                first.insert(key, w);
+            }
+        }
+    }
+    changed
+}
+
+impl Circuit {
+    /// Simplified copy of the circuit, only keeping what `outputs` depend
+    /// on. Overridden wires become constants. Gates are folded when all of
+    /// their inputs are constant, identities like `x AND 65535`, `x OR 0`,
+    /// `x LSHIFT 0` and `NOT NOT x` are removed and duplicate gates merged.
+    pub fn optimize(&self, outputs: &[&str]) -> Circuit {
+        let mut gates = self.gates.clone();
+        for (w, signal) in &self.overrides {
+            gates.insert(w.clone(), Gate::Assignment(Operand::Signal(*signal)));
+        }
+
+        while propagate(&mut gates) | simplify(&mut gates) | merge(&mut gates) {}
+
+        let mut live = HashSet::new();
+        let mut stack: Vec<&str> = outputs.to_vec();
+        while let Some(w) = stack.pop() {
+            if live.insert(w.to_string()) {
+                if let Some(gate) = gates.get(w) {
+                    stack.extend(gate.inputs().map(|i| i.as_str()));
+                }
+            }
+        }
+        gates.retain(|w, _| live.contains(w));
+
+        let mut circuit = Circuit::new();
+        circuit.gates = gates;
+        circuit
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs;
+
+    #[test]
+    fn test_fold() {
+        let input = fs::read_to_string("data/day7.txt").unwrap();
+        let mut circuit = Circuit::new();
+        circuit.load(input.trim().lines().collect());
+
+        let mut optimized = circuit.optimize(&["a"]);
+        assert_eq!("956 -> a\n", optimized.netlist());
+        assert_eq!(956, optimized.get_wire("a".to_string()));
+
+        circuit.set_wire("b".to_string(), 956);
+        assert_eq!("40149 -> a\n", circuit.optimize(&["a"]).netlist());
+    }
+
+    #[test]
+    fn test_simplify() {
+        let input = [
+            "x AND 65535 -> a",
+            "a OR 0 -> b",
+            "NOT b -> c",
+            "NOT c -> d",
+            "x AND y -> e",
+            "y AND x -> f",
+            "e OR f -> g",
+            "d LSHIFT 0 -> h",
+            "3 LSHIFT 2 -> k",
+            "h AND k -> out",
+            "g OR k -> out2",
+            "x OR y -> unused",
+        ];
+        let mut circuit = Circuit::new();
+        circuit.load(input.to_vec());
+
+        let mut optimized = circuit.optimize(&["out", "out2"]);
+        assert_eq!(
+            "x AND y -> e\nx AND 12 -> out\ne OR 12 -> out2\n",
+            optimized.netlist()
+        );
+
+        for (x, y) in [(0, 0), (123, 456), (u16::MAX, 7), (4, 8)] {
+            for c in [&mut circuit, &mut optimized] {
+                c.set_wire("x".to_string(), x);
+                c.set_wire("y".to_string(), y);
+            }
+            for out in ["out", "out2"] {
+                assert_eq!(
+                    circuit.get_wire(out.to_string()),
+                    optimized.get_wire(out.to_string())
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn test_netlist() {
+        let input = fs::read_to_string("data/day7.txt").unwrap();
+        let mut circuit = Circuit::new();
+        circuit.load(input.trim().lines().collect());
+
+        let netlist = circuit.netlist();
+        assert_eq!(input.trim().lines().count(), netlist.lines().count());
+
+        let mut reloaded = Circuit::new();
+        reloaded.load(netlist.lines().collect());
+        assert_eq!(circuit.gates, reloaded.gates);
+    }
+}
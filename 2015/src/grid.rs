@@ -1,4 +1,7 @@
-#[derive(PartialEq, Eq, Hash, Clone, Copy)]
+use std::collections::{HashMap, HashSet};
+use std::fmt::Display;
+
+#[derive(PartialEq, Eq, Hash, Clone, Copy, Debug)]
 pub struct Position {
     x: i64,
     y: i64,
@@ -9,6 +12,19 @@ impl Position {
         Position { x, y }
     }
 
+    pub fn x(&self) -> i64 {
+        self.x
+    }
+
+    pub fn y(&self) -> i64 {
+        self.y
+    }
+
+    /// Position reached after `steps` moves of `(dx, dy)` each.
+    pub fn offset(&self, dx: i64, dy: i64, steps: i64) -> Position {
+        Position::new(self.x + dx * steps, self.y + dy * steps)
+    }
+
     pub fn update(&mut self, c: char) -> Result<(), char> {
         match c {
             '>' => self.y += 1,
@@ -20,3 +36,377 @@ impl Position {
         Ok(())
     }
 }
+
+/// Number of visits received by every house along a route.
+#[derive(Default)]
+pub struct Houses {
+    visits: HashMap<Position, usize>,
+}
+
+impl Houses {
+    pub fn new() -> Houses {
+        Houses::default()
+    }
+
+    pub fn visit(&mut self, house: Position) {
+        *self.visits.entry(house).or_insert(0) += 1;
+    }
+
+    pub fn len(&self) -> usize {
+        self.visits.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.visits.is_empty()
+    }
+
+    pub fn visits(&self, house: &Position) -> usize {
+        self.visits.get(house).copied().unwrap_or(0)
+    }
+
+    /// Smallest and largest corners of the box holding every visited house.
+    pub fn bounds(&self) -> Option<(Position, Position)> {
+        let mut houses = self.visits.keys();
+        let first = *houses.next()?;
+
+        Some(houses.fold((first, first), |(min, max), h| {
+            (
+                Position::new(min.x.min(h.x), min.y.min(h.y)),
+                Position::new(max.x.max(h.x), max.y.max(h.y)),
+            )
+        }))
+    }
+
+    /// The `n` houses with the most visits, busiest first. Ties are broken
+    /// by position so the output is stable.
+    pub fn most_visited(&self, n: usize) -> Vec<(Position, usize)> {
+        let mut houses: Vec<(Position, usize)> =
+            self.visits.iter().map(|(h, v)| (*h, *v)).collect();
+        houses.sort_by_key(|(h, v)| (std::cmp::Reverse(*v), h.x, h.y));
+        houses.truncate(n);
+        houses
+    }
+
+    /// Rows of visit counts covering the bounding box. `^` moves along `x`
+    /// and `>` along `y`, so rows go from the largest `x` down and columns
+    /// from the smallest `y` up, keeping north on top and east on the right.
+    fn rows(&self) -> Vec<Vec<usize>> {
+        let (min, max) = match self.bounds() {
+            Some(b) => b,
+            None => return Vec::new(),
+        };
+
+        (min.x..=max.x)
+            .rev()
+            .map(|x| {
+                (min.y..=max.y)
+                    .map(|y| self.visits(&Position::new(x, y)))
+                    .collect()
+            })
+            .collect()
+    }
+
+    /// Text heatmap, one character per house, darker meaning busier.
+    pub fn render_text(&self) -> String {
+        const SHADES: &[u8] = b" .:-=+*#%@";
+
+        let max = self.visits.values().copied().max().unwrap_or(0);
+        let mut output = String::new();
+
+        for row in self.rows() {
+            for v in row {
+                let shade = if v == 0 {
+                    0
+                } else {
+                    1 + (v - 1) * (SHADES.len() - 2) / (max - 1).max(1)
+                };
+                output.push(SHADES[shade] as char);
+            }
+            output.push('\n');
+        }
+        output
+    }
+
+    /// Plain (P2) PGM heatmap, brighter meaning busier.
+    pub fn render_pgm(&self) -> String {
+        let rows = self.rows();
+        let max = self.visits.values().copied().max().unwrap_or(0);
+        let width = rows.first().map_or(0, |r| r.len());
+
+        // PGM requires a maxval of at least 1, even with nothing to draw.
+        let mut output = format!("P2\n{} {}\n{}\n", width, rows.len(), max.max(1));
+        for row in rows {
+            let row: Vec<String> = row.iter().map(|v| v.to_string()).collect();
+            output.push_str(&row.join(" "));
+            output.push('\n');
+        }
+        output
+    }
+}
+
+/// How instructions are handed out to the agents of a delivery.
+pub enum Dispatch {
+    /// Agents take turns, one instruction each.
+    RoundRobin,
+    /// Agents take turns, agent `i` following `weights[i]` instructions
+    /// in a row before handing over to the next one.
+    Weighted(Vec<usize>),
+    /// Every line of the input is the route of a single agent.
+    PerAgent,
+}
+
+#[derive(Debug, PartialEq)]
+pub enum DeliveryError {
+    InvalidInstruction(char),
+    /// Weights that don't give every agent a turn, or give no one any.
+    InvalidWeights,
+    /// Number of route lines that doesn't match the number of agents.
+    RouteCount {
+        agents: usize,
+        routes: usize,
+    },
+}
+
+impl Display for DeliveryError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DeliveryError::InvalidInstruction(c) => write!(f, "invalid instruction '{}'", c),
+            DeliveryError::InvalidWeights => write!(f, "weights don't match the agents"),
+            DeliveryError::RouteCount { agents, routes } => {
+                write!(f, "{} routes for {} agents", routes, agents)
+            }
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+pub struct Report {
+    pub unique: usize,
+    pub per_agent: Vec<usize>,
+    pub shared: usize,
+    pub positions: Vec<Position>,
+}
+
+pub struct Delivery {
+    agents: Vec<Position>,
+    visited: HashMap<Position, HashSet<usize>>,
+    houses: Houses,
+    dispatch: Dispatch,
+}
+
+impl Delivery {
+    pub fn new(agents: usize, dispatch: Dispatch) -> Delivery {
+        let agents = vec![Position::new(0, 0); agents];
+        let mut delivery = Delivery {
+            agents,
+            visited: HashMap::new(),
+            houses: Houses::new(),
+            dispatch,
+        };
+
+        for agent in 0..delivery.agents.len() {
+            delivery.visit(agent);
+        }
+        delivery
+    }
+
+    fn visit(&mut self, agent: usize) {
+        self.houses.visit(self.agents[agent]);
+        self.visited
+            .entry(self.agents[agent])
+            .or_default()
+            .insert(agent);
+    }
+
+    fn step(&mut self, agent: usize, c: char) -> Result<(), DeliveryError> {
+        self.agents[agent]
+            .update(c)
+            .map_err(DeliveryError::InvalidInstruction)?;
+        self.visit(agent);
+        Ok(())
+    }
+
+    pub fn run(&mut self, input: &str) -> Result<(), DeliveryError> {
+        let agents = self.agents.len();
+        if agents == 0 {
+            return Ok(());
+        }
+
+        match &self.dispatch {
+            Dispatch::RoundRobin => {
+                for (i, c) in input.trim().chars().enumerate() {
+                    self.step(i % agents, c)?;
+                }
+            }
+            Dispatch::Weighted(weights) => {
+                let weights = weights.clone();
+                if weights.len() != agents || weights.iter().all(|w| *w == 0) {
+                    return Err(DeliveryError::InvalidWeights);
+                }
+
+                let mut agent = 0;
+                let mut taken = 0;
+                for c in input.trim().chars() {
+                    while taken == weights[agent] {
+                        agent = (agent + 1) % agents;
+                        taken = 0;
+                    }
+                    self.step(agent, c)?;
+                    taken += 1;
+                }
+            }
+            Dispatch::PerAgent => {
+                let routes = input.trim().lines().count();
+                if routes != agents {
+                    return Err(DeliveryError::RouteCount { agents, routes });
+                }
+
+                for (agent, line) in input.trim().lines().enumerate() {
+                    for c in line.trim().chars() {
+                        self.step(agent, c)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn houses(&self) -> &Houses {
+        &self.houses
+    }
+
+    pub fn report(&self) -> Report {
+        let mut per_agent = vec![0; self.agents.len()];
+        let mut shared = 0;
+
+        for agents in self.visited.values() {
+            for agent in agents {
+                per_agent[*agent] += 1;
+            }
+
+            if agents.len() > 1 {
+                shared += 1;
+            }
+        }
+
+        Report {
+            unique: self.visited.len(),
+            per_agent,
+            shared,
+            positions: self.agents.clone(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deliver(agents: usize, dispatch: Dispatch, input: &str) -> Report {
+        let mut delivery = Delivery::new(agents, dispatch);
+        delivery.run(input).unwrap();
+        delivery.report()
+    }
+
+    #[test]
+    fn test_single_agent() {
+        assert_eq!(2, deliver(1, Dispatch::RoundRobin, ">").unique);
+        assert_eq!(4, deliver(1, Dispatch::RoundRobin, "^>v<").unique);
+        assert_eq!(2, deliver(1, Dispatch::RoundRobin, "^v^v^v^v^v").unique);
+    }
+
+    #[test]
+    fn test_round_robin() {
+        assert_eq!(3, deliver(2, Dispatch::RoundRobin, "^v").unique);
+        assert_eq!(3, deliver(2, Dispatch::RoundRobin, "^>v<").unique);
+        assert_eq!(11, deliver(2, Dispatch::RoundRobin, "^v^v^v^v^v").unique);
+
+        let report = deliver(2, Dispatch::RoundRobin, "^>v<");
+        assert_eq!(vec![2, 2], report.per_agent);
+        assert_eq!(1, report.shared);
+        assert_eq!(vec![Position::new(0, 0); 2], report.positions);
+    }
+
+    #[test]
+    fn test_weighted() {
+        let report = deliver(2, Dispatch::Weighted(vec![2, 1]), "^^>vv<");
+        assert_eq!(vec![3, 2], report.per_agent);
+        assert_eq!(
+            vec![Position::new(0, 0), Position::new(0, 0)],
+            report.positions
+        );
+
+        let mut delivery = Delivery::new(2, Dispatch::Weighted(vec![1]));
+        assert_eq!(Err(DeliveryError::InvalidWeights), delivery.run("^v"));
+    }
+
+    #[test]
+    fn test_per_agent() {
+        let report = deliver(3, Dispatch::PerAgent, ">>\n^^\n>");
+        assert_eq!(5, report.unique);
+        assert_eq!(vec![3, 3, 2], report.per_agent);
+        assert_eq!(2, report.shared);
+        assert_eq!(
+            vec![
+                Position::new(0, 2),
+                Position::new(2, 0),
+                Position::new(0, 1)
+            ],
+            report.positions
+        );
+
+        let mut delivery = Delivery::new(2, Dispatch::PerAgent);
+        assert_eq!(
+            Err(DeliveryError::RouteCount {
+                agents: 2,
+                routes: 3
+            }),
+            delivery.run(">>\n^^\n>")
+        );
+    }
+
+    #[test]
+    fn test_visits() {
+        let mut delivery = Delivery::new(1, Dispatch::RoundRobin);
+        delivery.run("^v^v^>").unwrap();
+        let houses = delivery.houses();
+
+        assert_eq!(3, houses.len());
+        assert_eq!(3, houses.visits(&Position::new(0, 0)));
+        assert_eq!(3, houses.visits(&Position::new(1, 0)));
+        assert_eq!(0, houses.visits(&Position::new(5, 5)));
+        assert_eq!(
+            Some((Position::new(0, 0), Position::new(1, 1))),
+            houses.bounds()
+        );
+        assert_eq!(
+            vec![(Position::new(0, 0), 3), (Position::new(1, 0), 3)],
+            houses.most_visited(2)
+        );
+    }
+
+    #[test]
+    fn test_render() {
+        let mut delivery = Delivery::new(1, Dispatch::RoundRobin);
+        delivery.run(">>^^<<").unwrap();
+        let houses = delivery.houses();
+
+        assert_eq!("...\n  .\n...\n", houses.render_text());
+        assert_eq!("P2\n3 3\n1\n1 1 1\n0 0 1\n1 1 1\n", houses.render_pgm());
+        assert_eq!("", Houses::new().render_text());
+        assert_eq!("P2\n0 0\n1\n", Houses::new().render_pgm());
+
+        let mut delivery = Delivery::new(1, Dispatch::RoundRobin);
+        delivery.run(">><<").unwrap();
+        assert_eq!("@@.\n", delivery.houses().render_text());
+    }
+
+    #[test]
+    fn test_invalid_instruction() {
+        let mut delivery = Delivery::new(2, Dispatch::RoundRobin);
+        assert_eq!(
+            Err(DeliveryError::InvalidInstruction('x')),
+            delivery.run("^x")
+        );
+    }
+}
@@ -0,0 +1,17 @@
+pub mod adventcoins;
+pub mod building;
+pub mod circuit;
+pub mod gifts;
+pub mod grid;
+pub mod hasher;
+pub mod lights;
+pub mod list;
+pub mod look_and_say;
+pub mod movement;
+pub mod naughtynice;
+pub mod ocr;
+pub mod password;
+pub mod path;
+pub mod route;
+pub mod rules;
+pub mod scanner;
@@ -1,16 +1,185 @@
-use std::usize;
+use std::fmt::Display;
+use std::num::ParseIntError;
+use std::str::FromStr;
 
-enum Instruction {
+pub mod compressed;
+pub mod history;
+pub mod life;
+pub mod query;
+
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum Instruction {
     Turn(bool, Range),
     Toggle(Range),
+    Set(u64, Range),
+    Dim(u64, Range),
+    /// Shifts row `y` right by `by` lights, wrapping around.
+    RotateRow {
+        y: usize,
+        by: usize,
+    },
+    /// Shifts column `x` down by `by` lights, wrapping around.
+    RotateColumn {
+        x: usize,
+        by: usize,
+    },
+}
+
+impl Instruction {
+    /// Rectangle the instruction changes in place, rotations move lights
+    /// around instead.
+    pub fn range(&self) -> Option<&Range> {
+        match self {
+            Instruction::Turn(_, r)
+            | Instruction::Toggle(r)
+            | Instruction::Set(_, r)
+            | Instruction::Dim(_, r) => Some(r),
+            Instruction::RotateRow { .. } | Instruction::RotateColumn { .. } => None,
+        }
+    }
+
+    /// New value of a light inside the instruction's range. Rotations leave
+    /// the value alone.
+    pub fn transform<S: Semantics>(&self, light: u64, semantics: &S) -> u64 {
+        match self {
+            Instruction::Turn(v, _) => semantics.turn(light, *v),
+            Instruction::Toggle(_) => semantics.toggle(light),
+            Instruction::Set(n, _) => semantics.set(light, *n),
+            Instruction::Dim(n, _) => semantics.dim(light, *n),
+            Instruction::RotateRow { .. } | Instruction::RotateColumn { .. } => light,
+        }
+    }
+
+    fn generate_turn(input: Vec<&str>) -> Result<Instruction, GridError> {
+        if input.len() != 5 {
+            return Err(GridError::SizeLengthError);
+        }
+
+        match input[1] {
+            "on" => Ok(Instruction::Turn(
+                true,
+                Range::new(input[2], input[3], input[4])?,
+            )),
+            "off" => Ok(Instruction::Turn(
+                false,
+                Range::new(input[2], input[3], input[4])?,
+            )),
+            _ => Err(GridError::InvalidInstruction),
+        }
+    }
+
+    fn generate_toggle(input: Vec<&str>) -> Result<Instruction, GridError> {
+        if input.len() != 4 {
+            return Err(GridError::SizeLengthError);
+        }
+
+        Ok(Instruction::Toggle(Range::new(
+            input[1], input[2], input[3],
+        )?))
+    }
+
+    /// `rect AxB` lights up a rectangle `A` wide and `B` tall at the top left.
+    fn generate_rect(input: Vec<&str>) -> Result<Instruction, GridError> {
+        if input.len() != 2 {
+            return Err(GridError::SizeLengthError);
+        }
+
+        let (a, b) = input[1]
+            .split_once('x')
+            .ok_or(GridError::InvalidInstruction)?;
+        let (a, b) = (a.parse::<usize>()?, b.parse::<usize>()?);
+        if a == 0 || b == 0 {
+            return Err(GridError::InvalidInstruction);
+        }
+
+        Ok(Instruction::Turn(
+            true,
+            Range::from_corners((0, 0), (a - 1, b - 1)),
+        ))
+    }
+
+    fn generate_rotate(input: Vec<&str>) -> Result<Instruction, GridError> {
+        if input.len() != 5 {
+            return Err(GridError::SizeLengthError);
+        }
+        if input[3] != "by" {
+            return Err(GridError::InvalidInstruction);
+        }
+
+        let by = input[4].parse::<usize>()?;
+        match (input[1], input[2].split_once('=')) {
+            ("row", Some(("y", y))) => Ok(Instruction::RotateRow {
+                y: y.parse::<usize>()?,
+                by,
+            }),
+            ("column", Some(("x", x))) => Ok(Instruction::RotateColumn {
+                x: x.parse::<usize>()?,
+                by,
+            }),
+            _ => Err(GridError::InvalidInstruction),
+        }
+    }
+
+    /// `set` and `dim`, both a range followed by a keyword and a number.
+    fn generate_level(input: Vec<&str>, keyword: &str) -> Result<Instruction, GridError> {
+        if input.len() != 6 {
+            return Err(GridError::SizeLengthError);
+        }
+        if input[4] != keyword {
+            return Err(GridError::InvalidInstruction);
+        }
+
+        let range = Range::new(input[1], input[2], input[3])?;
+        let n = input[5].parse::<u64>()?;
+        match input[0] {
+            "set" => Ok(Instruction::Set(n, range)),
+            _ => Ok(Instruction::Dim(n, range)),
+        }
+    }
 }
 
-struct Range {
+impl FromStr for Instruction {
+    type Err = GridError;
+
+    fn from_str(input: &str) -> Result<Self, Self::Err> {
+        let input: Vec<&str> = input.split(' ').collect();
+
+        match input[0] {
+            "turn" => Instruction::generate_turn(input),
+            "toggle" => Instruction::generate_toggle(input),
+            "rect" => Instruction::generate_rect(input),
+            "rotate" => Instruction::generate_rotate(input),
+            "set" => Instruction::generate_level(input, "to"),
+            "dim" => Instruction::generate_level(input, "by"),
+            _ => Err(GridError::InvalidInstruction),
+        }
+    }
+}
+
+/// Inclusive rectangle of lights, corners always ordered.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub struct Range {
     x: (usize, usize),
     y: (usize, usize),
 }
 
 impl Range {
+    /// Rectangle spanning two opposite corners, given in any order.
+    pub fn from_corners(a: (usize, usize), b: (usize, usize)) -> Range {
+        Range {
+            x: (a.0.min(b.0), a.0.max(b.0)),
+            y: (a.1.min(b.1), a.1.max(b.1)),
+        }
+    }
+
+    pub fn x(&self) -> (usize, usize) {
+        self.x
+    }
+
+    pub fn y(&self) -> (usize, usize) {
+        self.y
+    }
+
     fn new(lower: &str, thr: &str, upper: &str) -> Result<Range, GridError> {
         if thr != "through" {
             return Err(GridError::InvalidInstruction);
@@ -19,27 +188,141 @@ impl Range {
         let lower = Range::parse_bound(lower)?;
         let upper = Range::parse_bound(upper)?;
 
-        Ok(Range {
-            x: (lower.0, upper.0),
-            y: (lower.1, upper.1),
-        })
+        Ok(Range::from_corners(lower, upper))
     }
 
     fn parse_bound(input: &str) -> Result<(usize, usize), GridError> {
         let input: Vec<&str> = input.split(',').collect();
         match input[..] {
-            [x, y] => Ok((x.parse::<usize>().unwrap(), y.parse::<usize>().unwrap())),
+            [x, y] => Ok((x.parse::<usize>()?, y.parse::<usize>()?)),
             _ => Err(GridError::InvalidInstruction),
         }
     }
 }
 
-#[derive(Debug)]
-enum GridError {
+#[derive(PartialEq, Eq, Debug)]
+pub enum GridError {
     InvalidInstruction,
     SizeLengthError,
+    ParseError(ParseIntError),
+    InvalidCell(char),
+    /// Instruction the grid it was applied to can't carry out.
+    Unsupported,
+    /// Corner lying outside a grid of the given width and height.
+    OutOfBounds {
+        corner: (usize, usize),
+        size: (usize, usize),
+    },
+    /// Error on a given line, which is also the instruction's position.
+    Line(usize, Box<GridError>),
 }
 
+impl Display for GridError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            GridError::InvalidInstruction => write!(f, "invalid instruction"),
+            GridError::SizeLengthError => write!(f, "invalid number of words"),
+            GridError::ParseError(parse_int_error) => write!(f, "{}", parse_int_error),
+            GridError::InvalidCell(c) => write!(f, "invalid cell '{}'", c),
+            GridError::Unsupported => write!(f, "instruction not supported by this grid"),
+            GridError::OutOfBounds { corner, size } => write!(
+                f,
+                "{},{} is outside of a {}x{} grid",
+                corner.0, corner.1, size.0, size.1
+            ),
+            GridError::Line(line, e) => write!(f, "line {}: {}", line, e),
+        }
+    }
+}
+
+impl From<ParseIntError> for GridError {
+    fn from(e: ParseIntError) -> Self {
+        GridError::ParseError(e)
+    }
+}
+
+/// How a light reacts to instructions.
+pub trait Semantics {
+    fn turn(&self, light: u64, on: bool) -> u64;
+    fn toggle(&self, light: u64) -> u64;
+    fn set(&self, light: u64, value: u64) -> u64;
+    fn dim(&self, light: u64, by: u64) -> u64;
+}
+
+/// Lights are either on (1) or off (0).
+pub struct Boolean;
+
+impl Semantics for Boolean {
+    fn turn(&self, _: u64, on: bool) -> u64 {
+        on as u64
+    }
+
+    fn toggle(&self, light: u64) -> u64 {
+        (light == 0) as u64
+    }
+
+    fn set(&self, _: u64, value: u64) -> u64 {
+        (value > 0) as u64
+    }
+
+    fn dim(&self, light: u64, by: u64) -> u64 {
+        if by > 0 {
+            0
+        } else {
+            light
+        }
+    }
+}
+
+/// Turning on adds 1, turning off takes 1 down to 0 and toggling adds 2.
+pub struct Brightness;
+
+impl Semantics for Brightness {
+    fn turn(&self, light: u64, on: bool) -> u64 {
+        if on {
+            light + 1
+        } else {
+            light.saturating_sub(1)
+        }
+    }
+
+    fn toggle(&self, light: u64) -> u64 {
+        light + 2
+    }
+
+    fn set(&self, _: u64, value: u64) -> u64 {
+        value
+    }
+
+    fn dim(&self, light: u64, by: u64) -> u64 {
+        light.saturating_sub(by)
+    }
+}
+
+/// Parses one instruction per line, errors carrying the 1-based line.
+pub fn parse(input: &str) -> Result<Vec<Instruction>, GridError> {
+    input
+        .trim_end()
+        .lines()
+        .enumerate()
+        .map(|(i, line)| {
+            line.parse()
+                .map_err(|e| GridError::Line(i + 1, Box::new(e)))
+        })
+        .collect()
+}
+
+fn check_bounds(r: &Range, width: usize, height: usize) -> Result<(), GridError> {
+    if r.x.1 >= width || r.y.1 >= height {
+        return Err(GridError::OutOfBounds {
+            corner: (r.x.1, r.y.1),
+            size: (width, height),
+        });
+    }
+    Ok(())
+}
+
+#[derive(PartialEq, Eq, Debug, Clone)]
 pub struct Grid {
     lights: Vec<Vec<u64>>,
 }
@@ -51,73 +334,93 @@ impl Grid {
         Grid { lights }
     }
 
-    fn generate_turn(input: Vec<&str>) -> Result<Instruction, GridError> {
-        if input.len() != 5 {
-            return Err(GridError::SizeLengthError);
+    fn update(&mut self, r: &Range, f: impl Fn(u64) -> u64) {
+        for row in self.lights[r.y.0..=r.y.1].iter_mut() {
+            for light in row[r.x.0..=r.x.1].iter_mut() {
+                *light = f(*light);
+            }
         }
+    }
 
-        match input[1] {
-            "on" => Ok(Instruction::Turn(
-                true,
-                Range::new(input[2], input[3], input[4])?,
-            )),
-            "off" => Ok(Instruction::Turn(
-                false,
-                Range::new(input[2], input[3], input[4])?,
-            )),
-            _ => Err(GridError::InvalidInstruction),
-        }
+    fn sum(&self, r: &Range) -> usize {
+        self.lights[r.y.0..=r.y.1]
+            .iter()
+            .flat_map(|row| &row[r.x.0..=r.x.1])
+            .map(|light| *light as usize)
+            .sum()
     }
 
-    fn generate_toggle(input: Vec<&str>) -> Result<Instruction, GridError> {
-        if input.len() != 4 {
-            return Err(GridError::SizeLengthError);
-        }
+    pub fn get(&self, x: usize, y: usize) -> u64 {
+        self.lights[y][x]
+    }
 
-        Ok(Instruction::Toggle(Range::new(
-            input[1], input[2], input[3],
-        )?))
+    pub fn width(&self) -> usize {
+        self.lights.first().map_or(0, |row| row.len())
     }
 
-    fn parse(input: &str) -> Result<Instruction, GridError> {
-        let input: Vec<&str> = input.split(' ').collect();
+    pub fn height(&self) -> usize {
+        self.lights.len()
+    }
 
-        match input[0] {
-            "turn" => Grid::generate_turn(input),
-            "toggle" => Grid::generate_toggle(input),
-            _ => Err(GridError::InvalidInstruction),
-        }
+    fn check(&self, r: &Range) -> Result<(), GridError> {
+        check_bounds(r, self.width(), self.height())
     }
 
-    fn turn(&mut self, v: bool, r: Range) {
-        for row in self.lights[r.y.0..=r.y.1].iter_mut() {
-            for light in row[r.x.0..=r.x.1].iter_mut() {
-                if v {
-                    *light += 1;
-                } else if *light > 0 {
-                    *light -= 1;
-                }
-            }
+    /// Rectangle of lights `ins` may touch, rotations covering a whole row
+    /// or column.
+    fn extent(&self, ins: &Instruction) -> Range {
+        let (right, bottom) = (self.width().max(1) - 1, self.height().max(1) - 1);
+
+        match ins {
+            Instruction::RotateRow { y, .. } => Range::from_corners((0, *y), (right, *y)),
+            Instruction::RotateColumn { x, .. } => Range::from_corners((*x, 0), (*x, bottom)),
+            _ => *ins.range().unwrap(),
         }
     }
 
-    fn toggle(&mut self, r: Range) {
-        for row in self.lights[r.y.0..=r.y.1].iter_mut() {
-            for light in row[r.x.0..=r.x.1].iter_mut() {
-                *light += 2;
-            }
+    fn rotate_row(&mut self, y: usize, by: usize) {
+        let row = &mut self.lights[y];
+        let len = row.len();
+        row.rotate_right(by % len.max(1));
+    }
+
+    fn rotate_column(&mut self, x: usize, by: usize) {
+        let mut column: Vec<u64> = self.lights.iter().map(|row| row[x]).collect();
+        let len = column.len();
+        column.rotate_right(by % len.max(1));
+
+        for (row, light) in self.lights.iter_mut().zip(column) {
+            row[x] = light;
         }
     }
 
-    pub fn process(&mut self, input: &str) {
-        for line in input.trim().lines() {
-            let ins = Grid::parse(line).unwrap();
+    pub fn apply<S: Semantics>(
+        &mut self,
+        ins: &Instruction,
+        semantics: &S,
+    ) -> Result<(), GridError> {
+        self.check(&self.extent(ins))?;
 
-            match ins {
-                Instruction::Turn(v, r) => self.turn(v, r),
-                Instruction::Toggle(r) => self.toggle(r),
-            }
+        match ins {
+            Instruction::RotateRow { y, by } => self.rotate_row(*y, *by),
+            Instruction::RotateColumn { x, by } => self.rotate_column(*x, *by),
+            _ => self.update(&self.extent(ins), |light| ins.transform(light, semantics)),
         }
+        Ok(())
+    }
+
+    /// Applies every instruction in order, stopping at the first one that
+    /// doesn't fit the grid.
+    pub fn process<S: Semantics>(
+        &mut self,
+        instructions: &[Instruction],
+        semantics: &S,
+    ) -> Result<(), GridError> {
+        for (i, ins) in instructions.iter().enumerate() {
+            self.apply(ins, semantics)
+                .map_err(|e| GridError::Line(i + 1, Box::new(e)))?;
+        }
+        Ok(())
     }
 
     pub fn count(&self) -> usize {
@@ -130,3 +433,160 @@ impl Grid {
         c
     }
 }
+
+/// Draws lit lights as `#` and dark ones as `.`, one row per line.
+impl Display for Grid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in &self.lights {
+            let line: String = row.iter().map(|l| if *l > 0 { '#' } else { '.' }).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn count<S: Semantics>(input: &str, semantics: &S) -> usize {
+        let mut grid = Grid::new(1000, 1000);
+        grid.process(&parse(input).unwrap(), semantics).unwrap();
+        grid.count()
+    }
+
+    #[test]
+    fn test_boolean() {
+        assert_eq!(1_000_000, count("turn on 0,0 through 999,999", &Boolean));
+        assert_eq!(1000, count("toggle 0,0 through 999,0", &Boolean));
+        assert_eq!(
+            998_996,
+            count(
+                &[
+                    "turn on 0,0 through 999,999",
+                    "toggle 0,0 through 999,0",
+                    "turn off 499,499 through 500,500",
+                ]
+                .join("\n"),
+                &Boolean
+            )
+        );
+        assert_eq!(
+            0,
+            count("toggle 0,0 through 9,9\ntoggle 0,0 through 9,9", &Boolean)
+        );
+    }
+
+    #[test]
+    fn test_brightness() {
+        assert_eq!(1, count("turn on 0,0 through 0,0", &Brightness));
+        assert_eq!(2_000_000, count("toggle 0,0 through 999,999", &Brightness));
+        assert_eq!(0, count("turn off 0,0 through 9,9", &Brightness));
+        assert_eq!(
+            2,
+            count(
+                &[
+                    "toggle 0,0 through 0,0",
+                    "turn off 0,0 through 0,0",
+                    "turn on 0,0 through 0,0",
+                    "turn off 0,0 through 0,0",
+                    "turn on 0,0 through 0,0",
+                ]
+                .join("\n"),
+                &Brightness
+            )
+        );
+    }
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(
+            vec![
+                Instruction::Turn(true, Range::from_corners((1, 1), (5, 5))),
+                Instruction::Toggle(Range::from_corners((0, 9), (9, 0))),
+            ],
+            parse("turn on 5,5 through 1,1\ntoggle 9,0 through 0,9\n").unwrap()
+        );
+        assert_eq!(
+            Range::from_corners((1, 2), (3, 4)),
+            Range::from_corners((3, 2), (1, 4))
+        );
+
+        let error = parse("toggle 0,0 through 1,1\nturn on 0,x through 1,1").unwrap_err();
+        assert!(matches!(
+            &error,
+            GridError::Line(2, e) if matches!(**e, GridError::ParseError(_))
+        ));
+        assert_eq!(
+            "line 2: invalid instruction",
+            parse("toggle 0,0 through 1,1\nswitch 0,0 through 1,1")
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(
+            GridError::Line(1, Box::new(GridError::SizeLengthError)),
+            parse("turn on 0,0 through").unwrap_err()
+        );
+    }
+
+    #[test]
+    fn test_bounds() {
+        let mut grid = Grid::new(10, 5);
+        let instructions = parse("turn on 0,0 through 9,4\ntoggle 2,2 through 4,5").unwrap();
+
+        assert_eq!(
+            "line 2: 4,5 is outside of a 10x5 grid",
+            grid.process(&instructions, &Boolean)
+                .unwrap_err()
+                .to_string()
+        );
+        assert_eq!(50, grid.count());
+    }
+
+    #[test]
+    fn test_extended() {
+        let input = [
+            "rect 3x2",
+            "rotate column x=1 by 1",
+            "rotate row y=0 by 4",
+            "rotate column x=1 by 1",
+        ];
+        let mut grid = Grid::new(7, 3);
+        grid.process(&parse(&input.join("\n")).unwrap(), &Boolean)
+            .unwrap();
+
+        assert_eq!(6, grid.count());
+        assert_eq!(".#..#.#\n#.#....\n.#.....\n", grid.to_string());
+
+        let input = [
+            "set 0,0 through 3,3 to 5",
+            "dim 1,1 through 2,2 by 7",
+            "dim 0,0 through 0,3 by 2",
+            "rotate row y=0 by 9",
+        ];
+        let mut grid = Grid::new(4, 4);
+        grid.process(&parse(&input.join("\n")).unwrap(), &Brightness)
+            .unwrap();
+        assert_eq!(5 * 16 - 4 * 5 - 4 * 2, grid.count());
+        assert_eq!(3, grid.get(1, 0));
+        assert_eq!(5, grid.get(0, 0));
+
+        assert_eq!(
+            "line 1: 3,3 is outside of a 4x3 grid",
+            Grid::new(4, 3)
+                .process(&parse("rotate row y=3 by 1").unwrap(), &Boolean)
+                .unwrap_err()
+                .to_string()
+        );
+        for line in [
+            "rect 0x2",
+            "rotate row x=1 by 1",
+            "set 0,0 through 1,1 by 2",
+        ] {
+            assert_eq!(
+                GridError::Line(1, Box::new(GridError::InvalidInstruction)),
+                parse(line).unwrap_err()
+            );
+        }
+    }
+}
@@ -0,0 +1,192 @@
+use super::{GridError, Instruction, Range, Semantics};
+
+/// Light grid for huge coordinate spaces. Only the coordinates where some
+/// instruction starts or stops are kept, every cell between two of them
+/// standing in for a whole block of lights that always share a value.
+pub struct CompressedGrid {
+    xs: Vec<usize>,
+    ys: Vec<usize>,
+    lights: Vec<Vec<u64>>,
+}
+
+/// Coordinates where a block starts. Bounds reaching `usize::MAX` have no
+/// coordinate past them, so they're left out.
+fn breakpoints(bounds: impl Iterator<Item = (usize, usize)>) -> Vec<usize> {
+    let mut points: Vec<usize> = bounds
+        .filter_map(|(lo, hi)| Some([lo, hi.checked_add(1)?]))
+        .flatten()
+        .collect();
+    points.sort_unstable();
+    points.dedup();
+    points
+}
+
+/// Cells covering `lo..=hi`, as long as both ends fall on block edges.
+fn cells(points: &[usize], (lo, hi): (usize, usize)) -> Result<std::ops::Range<usize>, GridError> {
+    let end = hi.checked_add(1).ok_or(GridError::Unsupported)?;
+
+    match (points.binary_search(&lo), points.binary_search(&end)) {
+        (Ok(start), Ok(end)) => Ok(start..end),
+        _ => Err(GridError::Unsupported),
+    }
+}
+
+impl CompressedGrid {
+    /// Builds a grid fitting `instructions`. Other instructions can only be
+    /// processed if their edges line up with those of `instructions`.
+    pub fn new(instructions: &[Instruction]) -> CompressedGrid {
+        let ranges: Vec<&Range> = instructions.iter().filter_map(|ins| ins.range()).collect();
+        let xs = breakpoints(ranges.iter().map(|r| r.x));
+        let ys = breakpoints(ranges.iter().map(|r| r.y));
+
+        let lights = vec![vec![0; xs.len().saturating_sub(1)]; ys.len().saturating_sub(1)];
+
+        CompressedGrid { xs, ys, lights }
+    }
+
+    /// Rotations move lights across cell boundaries, so they're rejected,
+    /// as are ranges that don't line up with the cells.
+    pub fn apply<S: Semantics>(
+        &mut self,
+        ins: &Instruction,
+        semantics: &S,
+    ) -> Result<(), GridError> {
+        let r = ins.range().ok_or(GridError::Unsupported)?;
+        let xs = cells(&self.xs, r.x)?;
+        let ys = cells(&self.ys, r.y)?;
+
+        for row in self.lights[ys].iter_mut() {
+            for light in row[xs.clone()].iter_mut() {
+                *light = ins.transform(*light, semantics);
+            }
+        }
+        Ok(())
+    }
+
+    pub fn process<S: Semantics>(
+        &mut self,
+        instructions: &[Instruction],
+        semantics: &S,
+    ) -> Result<(), GridError> {
+        for (i, ins) in instructions.iter().enumerate() {
+            self.apply(ins, semantics)
+                .map_err(|e| GridError::Line(i + 1, Box::new(e)))?;
+        }
+        Ok(())
+    }
+
+    /// Same as `Grid::count`, each cell weighted by the number of lights it
+    /// stands for. A single cell can hold 10^18 lights, so the total is a
+    /// `u128`, enough for any brightness over a 10^9 square.
+    pub fn count(&self) -> u128 {
+        let mut c: u128 = 0;
+        for (y, row) in self.lights.iter().enumerate() {
+            let height = (self.ys[y + 1] - self.ys[y]) as u128;
+
+            for (x, light) in row.iter().enumerate() {
+                let width = (self.xs[x + 1] - self.xs[x]) as u128;
+                c += *light as u128 * width * height;
+            }
+        }
+        c
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse, Boolean, Brightness, Grid};
+    use super::*;
+
+    #[test]
+    fn test_matches_dense() {
+        let input = [
+            "turn on 0,0 through 9,9",
+            "toggle 3,2 through 7,15",
+            "turn off 5,5 through 19,6",
+            "toggle 0,0 through 19,19",
+            "turn on 12,1 through 12,18",
+            "turn off 2,3 through 4,19",
+            "toggle 6,6 through 6,6",
+            "set 1,1 through 8,8 to 3",
+            "dim 0,4 through 19,4 by 2",
+            "rect 2x3",
+        ]
+        .join("\n");
+        let instructions = parse(&input).unwrap();
+
+        let mut dense = Grid::new(20, 20);
+        let mut compressed = CompressedGrid::new(&instructions);
+        dense.process(&instructions, &Boolean).unwrap();
+        compressed.process(&instructions, &Boolean).unwrap();
+        assert_eq!(dense.count() as u128, compressed.count());
+
+        let mut dense = Grid::new(20, 20);
+        let mut compressed = CompressedGrid::new(&instructions);
+        dense.process(&instructions, &Brightness).unwrap();
+        compressed.process(&instructions, &Brightness).unwrap();
+        assert_eq!(dense.count() as u128, compressed.count());
+    }
+
+    #[test]
+    fn test_huge_coordinates() {
+        let input = [
+            "turn on 0,0 through 999999999,999999999",
+            "toggle 1,1 through 999999998,999999998",
+            "turn off 0,0 through 0,999999999",
+        ]
+        .join("\n");
+        let instructions = parse(&input).unwrap();
+
+        let mut grid = CompressedGrid::new(&instructions);
+        grid.process(&instructions, &Boolean).unwrap();
+        assert_eq!(4 * 999_999_999 - 1_000_000_000, grid.count());
+
+        let mut grid = CompressedGrid::new(&instructions);
+        grid.process(&instructions, &Brightness).unwrap();
+        assert_eq!(
+            1_000_000_000 * 1_000_000_000 + 2 * 999_999_998 * 999_999_998 - 1_000_000_000,
+            grid.count()
+        );
+    }
+
+    #[test]
+    fn test_huge_brightness() {
+        let input = ["toggle 0,0 through 999999999,999999999"; 10].join("\n");
+        let instructions = parse(&input).unwrap();
+
+        let mut grid = CompressedGrid::new(&instructions);
+        grid.process(&instructions, &Brightness).unwrap();
+        assert_eq!(20 * 1_000_000_000_000_000_000, grid.count());
+    }
+
+    #[test]
+    fn test_unsupported() {
+        let instructions = parse("rect 2x2\nrotate row y=1 by 1").unwrap();
+        let mut grid = CompressedGrid::new(&instructions);
+
+        assert_eq!(
+            GridError::Line(2, Box::new(GridError::Unsupported)),
+            grid.process(&instructions, &Boolean).unwrap_err()
+        );
+
+        // Ranges the grid wasn't built for only work along existing edges.
+        let instructions = parse("turn on 0,0 through 3,3\ntoggle 0,0 through 9,1").unwrap();
+        let mut grid = CompressedGrid::new(&instructions);
+        grid.process(&instructions, &Boolean).unwrap();
+        for other in ["toggle 1,1 through 2,2", "toggle 4,0 through 8,1"] {
+            let ins = parse(other).unwrap();
+            assert_eq!(Err(GridError::Unsupported), grid.apply(&ins[0], &Boolean));
+        }
+        let ins = parse("turn off 0,2 through 9,3").unwrap();
+        grid.apply(&ins[0], &Boolean).unwrap();
+        assert_eq!(12, grid.count());
+
+        let max = format!("turn on 0,0 through {},0", usize::MAX);
+        let instructions = parse(&max).unwrap();
+        let mut grid = CompressedGrid::new(&instructions);
+        assert_eq!(
+            Err(GridError::Unsupported),
+            grid.apply(&instructions[0], &Boolean)
+        );
+    }
+}
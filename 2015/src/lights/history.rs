@@ -0,0 +1,211 @@
+use super::{Grid, GridError, Instruction, Range, Semantics};
+
+/// Records every instruction applied to a grid so earlier states can be
+/// inspected. A copy of the grid is kept every `interval` instructions, so
+/// rebuilding any state replays at most `interval - 1` instructions.
+pub struct History<S: Semantics> {
+    semantics: S,
+    interval: usize,
+    instructions: Vec<Instruction>,
+    snapshots: Vec<Grid>,
+    counts: Vec<usize>,
+    current: Grid,
+}
+
+impl<S: Semantics> History<S> {
+    pub fn new(x: usize, y: usize, semantics: S, interval: usize) -> History<S> {
+        let grid = Grid::new(x, y);
+
+        History {
+            semantics,
+            interval: interval.max(1),
+            instructions: Vec::new(),
+            snapshots: vec![grid.clone()],
+            counts: vec![0],
+            current: grid,
+        }
+    }
+
+    pub fn push(&mut self, ins: Instruction) -> Result<(), GridError> {
+        let r = self.current.extent(&ins);
+        self.current.check(&r)?;
+        let before = self.current.sum(&r);
+
+        self.current.apply(&ins, &self.semantics)?;
+
+        let count = self.counts[self.counts.len() - 1] + self.current.sum(&r) - before;
+        self.counts.push(count);
+        self.instructions.push(ins);
+
+        if self.instructions.len().is_multiple_of(self.interval) {
+            self.snapshots.push(self.current.clone());
+        }
+        Ok(())
+    }
+
+    /// Same as `Grid::process`, keeping every instruction that was applied.
+    pub fn process(&mut self, instructions: &[Instruction]) -> Result<(), GridError> {
+        for (i, ins) in instructions.iter().enumerate() {
+            self.push(*ins)
+                .map_err(|e| GridError::Line(i + 1, Box::new(e)))?;
+        }
+        Ok(())
+    }
+
+    pub fn len(&self) -> usize {
+        self.instructions.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.instructions.is_empty()
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.current
+    }
+
+    /// Grid as it was after the first `k` instructions.
+    pub fn state_after(&self, k: usize) -> Option<Grid> {
+        if k > self.len() {
+            return None;
+        }
+
+        let start = k / self.interval * self.interval;
+        let mut grid = self.snapshots[k / self.interval].clone();
+        for ins in &self.instructions[start..k] {
+            grid.apply(ins, &self.semantics).unwrap();
+        }
+        Some(grid)
+    }
+
+    /// `count()` after the first `k` instructions, without rebuilding the grid.
+    pub fn count_after(&self, k: usize) -> Option<usize> {
+        self.counts.get(k).copied()
+    }
+
+    /// Change in `count()` caused by each instruction.
+    pub fn deltas(&self) -> Vec<i64> {
+        self.counts
+            .windows(2)
+            .map(|w| w[1] as i64 - w[0] as i64)
+            .collect()
+    }
+
+    /// Index of the last instruction that changed the light at `x,y`, if any
+    /// did. Rotations can carry other lights' values into `x,y`, so whole
+    /// grids are replayed, one interval at a time from the newest snapshot
+    /// back, stopping at the first interval holding a change.
+    pub fn last_changed(&self, x: usize, y: usize) -> Result<Option<usize>, GridError> {
+        self.current.check(&Range::from_corners((x, y), (x, y)))?;
+
+        for (k, snapshot) in self.snapshots.iter().enumerate().rev() {
+            let start = k * self.interval;
+            let end = (start + self.interval).min(self.len());
+            let mut grid = snapshot.clone();
+            let mut last = None;
+
+            for (i, ins) in self.instructions[start..end].iter().enumerate() {
+                let light = grid.get(x, y);
+                grid.apply(ins, &self.semantics).unwrap();
+
+                if grid.get(x, y) != light {
+                    last = Some(start + i);
+                }
+            }
+
+            if last.is_some() {
+                return Ok(last);
+            }
+        }
+        Ok(None)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse, Boolean, Brightness};
+    use super::*;
+
+    const INPUT: [&str; 6] = [
+        "turn on 0,0 through 9,9",
+        "toggle 0,0 through 9,0",
+        "turn off 4,4 through 5,5",
+        "turn on 0,0 through 3,3",
+        "toggle 2,2 through 7,7",
+        "turn off 9,9 through 9,9",
+    ];
+
+    #[test]
+    fn test_state_after() {
+        let instructions = parse(&INPUT.join("\n")).unwrap();
+
+        for interval in [1, 2, 4, 10] {
+            let mut history = History::new(10, 10, Brightness, interval);
+            history.process(&instructions).unwrap();
+
+            for k in 0..=instructions.len() {
+                let mut grid = Grid::new(10, 10);
+                grid.process(&instructions[..k], &Brightness).unwrap();
+
+                assert_eq!(Some(&grid), history.state_after(k).as_ref());
+                assert_eq!(Some(grid.count()), history.count_after(k));
+            }
+            assert_eq!(None, history.state_after(instructions.len() + 1));
+        }
+    }
+
+    #[test]
+    fn test_deltas() {
+        let mut history = History::new(10, 10, Boolean, 3);
+        history.process(&parse(&INPUT.join("\n")).unwrap()).unwrap();
+
+        assert_eq!(vec![100, -10, -4, 4, -28, -1], history.deltas());
+        assert_eq!(
+            history.grid().count() as i64,
+            history.deltas().iter().sum::<i64>()
+        );
+    }
+
+    #[test]
+    fn test_last_changed() {
+        for interval in [1, 3, 4, 10] {
+            let mut history = History::new(10, 10, Boolean, interval);
+            history.process(&parse(&INPUT.join("\n")).unwrap()).unwrap();
+
+            assert_eq!(Ok(Some(0)), history.last_changed(9, 5));
+            assert_eq!(Ok(Some(3)), history.last_changed(0, 0));
+            assert_eq!(Ok(Some(4)), history.last_changed(5, 5));
+            assert_eq!(Ok(Some(5)), history.last_changed(9, 9));
+            // Turned on again while already on, which doesn't count.
+            assert_eq!(Ok(Some(0)), history.last_changed(1, 1));
+        }
+
+        let mut history = History::new(10, 10, Boolean, 3);
+        history
+            .process(&parse("turn off 0,0 through 1,1").unwrap())
+            .unwrap();
+        assert_eq!(Ok(None), history.last_changed(0, 0));
+        assert_eq!(
+            Err(GridError::OutOfBounds {
+                corner: (10, 10),
+                size: (10, 10)
+            }),
+            history.last_changed(10, 10)
+        );
+    }
+
+    #[test]
+    fn test_rotations() {
+        let input = "rect 3x1\nrotate row y=0 by 1\nrotate column x=3 by 1\nrotate row y=0 by 3";
+        let mut history = History::new(4, 2, Boolean, 2);
+        history.process(&parse(input).unwrap()).unwrap();
+
+        assert_eq!(vec![3, 0, 0, 0], history.deltas());
+        assert_eq!("##..\n...#\n", history.grid().to_string());
+        assert_eq!(Ok(Some(2)), history.last_changed(3, 1));
+        assert_eq!(Ok(Some(3)), history.last_changed(0, 0));
+        assert_eq!(Ok(Some(0)), history.last_changed(1, 0));
+        assert_eq!(Ok(Some(3)), history.last_changed(2, 0));
+        assert_eq!(Ok(None), history.last_changed(0, 1));
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt::Display;
+use std::str::FromStr;
+
+use super::GridError;
+
+/// Conway-style animation: a lit light with 2 or 3 lit neighbours stays
+/// lit, an unlit one with exactly 3 lights up, everything else goes dark.
+/// Pinned lights stay lit no matter what.
+pub struct Life {
+    width: usize,
+    height: usize,
+    lights: Vec<bool>,
+    next: Vec<bool>,
+    pinned: Vec<(usize, usize)>,
+}
+
+impl Life {
+    pub fn new(x: usize, y: usize) -> Life {
+        Life {
+            width: x,
+            height: y,
+            lights: vec![false; x * y],
+            next: vec![false; x * y],
+            pinned: Vec::new(),
+        }
+    }
+
+    pub fn get(&self, x: usize, y: usize) -> bool {
+        self.lights[y * self.width + x]
+    }
+
+    pub fn set(&mut self, x: usize, y: usize, on: bool) {
+        self.lights[y * self.width + x] = on;
+    }
+
+    /// Keeps the light at `x,y` lit for good.
+    pub fn pin(&mut self, x: usize, y: usize) {
+        self.set(x, y, true);
+        self.pinned.push((x, y));
+    }
+
+    pub fn pin_corners(&mut self) {
+        if self.width == 0 || self.height == 0 {
+            return;
+        }
+
+        let (x, y) = (self.width - 1, self.height - 1);
+        for (cx, cy) in [(0, 0), (x, 0), (0, y), (x, y)] {
+            self.pin(cx, cy);
+        }
+    }
+
+    fn neighbours(&self, x: usize, y: usize) -> usize {
+        let mut n = 0;
+        for ny in y.saturating_sub(1)..=(y + 1).min(self.height - 1) {
+            for nx in x.saturating_sub(1)..=(x + 1).min(self.width - 1) {
+                if (nx, ny) != (x, y) && self.get(nx, ny) {
+                    n += 1;
+                }
+            }
+        }
+        n
+    }
+
+    pub fn step(&mut self) {
+        for y in 0..self.height {
+            for x in 0..self.width {
+                self.next[y * self.width + x] =
+                    matches!((self.get(x, y), self.neighbours(x, y)), (true, 2) | (_, 3));
+            }
+        }
+
+        for (x, y) in &self.pinned {
+            self.next[y * self.width + x] = true;
+        }
+        std::mem::swap(&mut self.lights, &mut self.next);
+    }
+
+    pub fn run(&mut self, generations: usize) {
+        for _ in 0..generations {
+            self.step();
+        }
+    }
+
+    pub fn count(&self) -> usize {
+        self.lights.iter().filter(|on| **on).count()
+    }
+}
+
+/// Reads one row per line, `#` for lit lights and `.` for unlit ones.
+impl FromStr for Life {
+    type Err = GridError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let rows: Vec<&str> = s.trim().lines().map(|line| line.trim()).collect();
+        let width = rows.first().map_or(0, |row| row.len());
+        let mut life = Life::new(width, rows.len());
+
+        for (y, row) in rows.iter().enumerate() {
+            let error = |e| GridError::Line(y + 1, Box::new(e));
+
+            if row.len() != width {
+                return Err(error(GridError::SizeLengthError));
+            }
+
+            for (x, c) in row.chars().enumerate() {
+                match c {
+                    '#' => life.set(x, y, true),
+                    '.' => {}
+                    c => return Err(error(GridError::InvalidCell(c))),
+                }
+            }
+        }
+        Ok(life)
+    }
+}
+
+impl Display for Life {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for row in self.lights.chunks(self.width.max(1)) {
+            let line: String = row.iter().map(|on| if *on { '#' } else { '.' }).collect();
+            writeln!(f, "{}", line)?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const INPUT: &str = ".#.#.#\n...##.\n#....#\n..#...\n#.#..#\n####..";
+
+    #[test]
+    fn test_run() {
+        let mut life: Life = INPUT.parse().unwrap();
+        assert_eq!(15, life.count());
+
+        life.step();
+        assert_eq!(
+            "..##..\n..##.#\n...##.\n......\n#.....\n#.##..\n",
+            life.to_string()
+        );
+
+        life.run(3);
+        assert_eq!(4, life.count());
+        assert_eq!(
+            "......\n......\n..##..\n..##..\n......\n......\n",
+            life.to_string()
+        );
+    }
+
+    #[test]
+    fn test_pinned() {
+        let mut life: Life = INPUT.parse().unwrap();
+        life.pin_corners();
+        assert_eq!(17, life.count());
+
+        life.run(5);
+        assert_eq!(17, life.count());
+        assert_eq!(
+            "##.###\n.##..#\n.##...\n.##...\n#.#...\n##...#\n",
+            life.to_string()
+        );
+    }
+
+    #[test]
+    fn test_errors() {
+        assert_eq!(
+            "line 2: invalid cell 'x'",
+            "#.\n.x".parse::<Life>().err().unwrap().to_string()
+        );
+        assert_eq!(
+            GridError::Line(3, Box::new(GridError::SizeLengthError)),
+            "#.\n..\n#".parse::<Life>().err().unwrap()
+        );
+    }
+}
@@ -0,0 +1,196 @@
+use super::{check_bounds, Grid, GridError, Instruction, Range, Semantics};
+
+/// Total brightness of every rectangle anchored at the top left corner,
+/// built once from a finished grid. Any `Range` then sums in constant time.
+pub struct SummedArea {
+    width: usize,
+    height: usize,
+    sums: Vec<u64>,
+}
+
+impl SummedArea {
+    pub fn new(grid: &Grid) -> SummedArea {
+        let (width, height) = (grid.width(), grid.height());
+        let mut sums = vec![0; (width + 1) * (height + 1)];
+
+        for (y, row) in grid.lights.iter().enumerate() {
+            let mut acc = 0;
+            for (x, light) in row.iter().enumerate() {
+                acc += light;
+                sums[(y + 1) * (width + 1) + x + 1] = sums[y * (width + 1) + x + 1] + acc;
+            }
+        }
+
+        SummedArea {
+            width,
+            height,
+            sums,
+        }
+    }
+
+    fn at(&self, x: usize, y: usize) -> u64 {
+        self.sums[y * (self.width + 1) + x]
+    }
+
+    pub fn sum(&self, r: &Range) -> Result<u64, GridError> {
+        check_bounds(r, self.width, self.height)?;
+
+        let (x0, x1) = (r.x.0, r.x.1 + 1);
+        let (y0, y1) = (r.y.0, r.y.1 + 1);
+        Ok(self.at(x1, y1) + self.at(x0, y0) - self.at(x0, y1) - self.at(x1, y0))
+    }
+}
+
+/// Light grid keeping a 2D Fenwick tree of its brightness up to date, so
+/// rectangles can be summed in O(log² n) while instructions keep coming.
+pub struct IndexedGrid<S: Semantics> {
+    grid: Grid,
+    semantics: S,
+    tree: Vec<Vec<i64>>,
+}
+
+impl<S: Semantics> IndexedGrid<S> {
+    pub fn new(x: usize, y: usize, semantics: S) -> IndexedGrid<S> {
+        IndexedGrid {
+            grid: Grid::new(x, y),
+            semantics,
+            tree: vec![vec![0; x + 1]; y + 1],
+        }
+    }
+
+    pub fn grid(&self) -> &Grid {
+        &self.grid
+    }
+
+    fn add(&mut self, x: usize, y: usize, delta: i64) {
+        let mut i = y + 1;
+        while i < self.tree.len() {
+            let row = &mut self.tree[i];
+            let mut j = x + 1;
+            while j < row.len() {
+                row[j] += delta;
+                j += j & j.wrapping_neg();
+            }
+            i += i & i.wrapping_neg();
+        }
+    }
+
+    /// Sum of every light with a coordinate below `x,y`.
+    fn prefix(&self, x: usize, y: usize) -> i64 {
+        let mut total = 0;
+        let mut i = y;
+        while i > 0 {
+            let mut j = x;
+            while j > 0 {
+                total += self.tree[i][j];
+                j -= j & j.wrapping_neg();
+            }
+            i -= i & i.wrapping_neg();
+        }
+        total
+    }
+
+    pub fn apply(&mut self, ins: &Instruction) -> Result<(), GridError> {
+        let r = self.grid.extent(ins);
+        self.grid.check(&r)?;
+
+        let before: Vec<Vec<u64>> = self.grid.lights[r.y.0..=r.y.1]
+            .iter()
+            .map(|row| row[r.x.0..=r.x.1].to_vec())
+            .collect();
+        self.grid.apply(ins, &self.semantics)?;
+
+        for (dy, row) in before.iter().enumerate() {
+            for (dx, light) in row.iter().enumerate() {
+                let (x, y) = (r.x.0 + dx, r.y.0 + dy);
+                let value = self.grid.lights[y][x];
+
+                if value != *light {
+                    self.add(x, y, value as i64 - *light as i64);
+                }
+            }
+        }
+        Ok(())
+    }
+
+    pub fn process(&mut self, instructions: &[Instruction]) -> Result<(), GridError> {
+        for (i, ins) in instructions.iter().enumerate() {
+            self.apply(ins)
+                .map_err(|e| GridError::Line(i + 1, Box::new(e)))?;
+        }
+        Ok(())
+    }
+
+    pub fn sum(&self, r: &Range) -> Result<u64, GridError> {
+        self.grid.check(r)?;
+
+        let (x0, x1) = (r.x.0, r.x.1 + 1);
+        let (y0, y1) = (r.y.0, r.y.1 + 1);
+        let total =
+            self.prefix(x1, y1) + self.prefix(x0, y0) - self.prefix(x0, y1) - self.prefix(x1, y0);
+        Ok(total as u64)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::{parse, Brightness};
+    use super::*;
+
+    const INPUT: [&str; 8] = [
+        "turn on 0,0 through 11,7",
+        "toggle 3,2 through 9,6",
+        "turn off 0,0 through 4,4",
+        "toggle 11,0 through 11,7",
+        "turn on 5,5 through 5,5",
+        "rotate row y=5 by 3",
+        "rotate column x=4 by 10",
+        "set 6,0 through 8,7 to 9",
+    ];
+
+    fn ranges() -> Vec<Range> {
+        let mut ranges = Vec::new();
+        for (x0, x1) in [(0, 0), (0, 11), (2, 7), (5, 5), (10, 11)] {
+            for (y0, y1) in [(0, 0), (0, 7), (1, 4), (6, 7)] {
+                ranges.push(Range::from_corners((x0, y0), (x1, y1)));
+            }
+        }
+        ranges
+    }
+
+    #[test]
+    fn test_summed_area() {
+        let mut grid = Grid::new(12, 8);
+        grid.process(&parse(&INPUT.join("\n")).unwrap(), &Brightness)
+            .unwrap();
+        let table = SummedArea::new(&grid);
+
+        for r in ranges() {
+            assert_eq!(grid.sum(&r) as u64, table.sum(&r).unwrap());
+        }
+        assert_eq!(
+            grid.count() as u64,
+            table.sum(&Range::from_corners((0, 0), (11, 7))).unwrap()
+        );
+        assert!(table.sum(&Range::from_corners((0, 0), (12, 0))).is_err());
+    }
+
+    #[test]
+    fn test_indexed() {
+        let instructions = parse(&INPUT.join("\n")).unwrap();
+        let mut grid = Grid::new(12, 8);
+        let mut indexed = IndexedGrid::new(12, 8, Brightness);
+
+        // Checked after every instruction, not only at the end.
+        for ins in &instructions {
+            grid.apply(ins, &Brightness).unwrap();
+            indexed.apply(ins).unwrap();
+
+            for r in ranges() {
+                assert_eq!(grid.sum(&r) as u64, indexed.sum(&r).unwrap());
+            }
+        }
+        assert_eq!(&grid, indexed.grid());
+        assert!(indexed.sum(&Range::from_corners((0, 0), (0, 8))).is_err());
+    }
+}
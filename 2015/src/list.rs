@@ -64,7 +64,7 @@ fn encode(input: &[u8]) -> Vec<u8> {
 }
 
 fn hex_to_ascii(hex: u8) -> u8 {
-    if (b'0'..=b'9').contains(&hex) {
+    if hex.is_ascii_digit() {
         hex - b'0'
     } else {
         hex - b'a'
@@ -1,34 +1,17 @@
-use std::collections::HashSet;
-use std::fs;
+use std::fs::{self, File};
+use std::io::BufReader;
 use std::str::FromStr;
 
-mod building;
-use building::{get_basement, get_final_floor};
-
-mod gifts;
-use gifts::Gift;
-
-mod grid;
-use grid::Position;
-
-mod adventcoins;
-use adventcoins::AdventCoins;
-
-mod naughtynice;
-
-mod lights;
-use lights::Grid;
-
-mod circuit;
-use circuit::Circuit;
-
-mod list;
-
-mod path;
-use path::{find_longest_path, find_shortest_path};
-
-mod look_and_say;
-mod password;
+use aoc_rs::adventcoins::{AdventCoins, Target};
+use aoc_rs::building::{get_basement, get_final_floor};
+use aoc_rs::circuit::Circuit;
+use aoc_rs::gifts::Gift;
+use aoc_rs::grid::{Delivery, Dispatch};
+use aoc_rs::lights::{self, Boolean, Brightness, Grid};
+use aoc_rs::naughtynice::RuleSet;
+use aoc_rs::path::{find_longest_path, find_shortest_path};
+use aoc_rs::scanner::Scanner;
+use aoc_rs::{list, look_and_say, password};
 
 fn main() {
     // Building stuff
@@ -64,48 +47,57 @@ fn main() {
 
     // Grid stuff
     let travel = fs::read_to_string("data/day3.txt").unwrap();
-    let mut santa = Position::new(0, 0);
-    let mut robo_santa = Position::new(0, 0);
-    let mut houses = HashSet::new();
-
-    houses.insert(santa);
-
-    for (i, c) in travel.trim().chars().enumerate() {
-        let santa: &mut Position = if i % 2 == 0 {
-            &mut santa
-        } else {
-            &mut robo_santa
-        };
-
-        if let Err(c) = santa.update(c) {
-            println!("Failed to parse {}", c);
-            continue;
-        }
-        houses.insert(*santa);
+    let mut santa = Delivery::new(1, Dispatch::RoundRobin);
+    if let Err(e) = santa.run(&travel) {
+        println!("Failed to deliver: {}", e);
+    }
+    println!("houses: {}", santa.report().unique);
+
+    let mut santas = Delivery::new(2, Dispatch::RoundRobin);
+    if let Err(e) = santas.run(&travel) {
+        println!("Failed to deliver: {}", e);
+    }
+    let report = santas.report();
+    println!("houses with robo-santa: {}", report.unique);
+    println!("shared houses: {}", report.shared);
+    if let Some((_, visits)) = santas.houses().most_visited(1).first() {
+        println!("busiest house visits: {}", visits);
     }
-    println!("houses: {}", houses.len());
 
     // AdventCoins stuff
-    let miner = AdventCoins::new("yzbqklnj", "00000");
-    println!("AdventCoin: {}", miner.mine());
+    let miner = AdventCoins::new("yzbqklnj", &[Target::zeros(5), Target::zeros(6)]);
+    match miner.mine() {
+        Ok(coins) => {
+            println!("AdventCoin: {}", coins[0]);
+            println!("AdventCoin with six zeros: {}", coins[1]);
+        }
+        Err(e) => println!("Failed to mine: {}", e),
+    }
 
     // NaughtyNice stuff
-    let list = fs::read_to_string("data/day5.txt").unwrap();
-    let mut count = 0;
-
-    for line in list.lines() {
-        if naughtynice::validate(line) == naughtynice::Value::Nice {
-            count += 1;
-        }
+    for (name, rules) in [
+        ("Nice", RuleSet::Original),
+        ("Revised nice", RuleSet::Revised),
+    ] {
+        let rule = rules.rule();
+        let scanner = Scanner::new(rule.as_ref());
+        let list = BufReader::new(File::open("data/day5.txt").unwrap());
+        let tally = scanner.scan(list, 4).unwrap();
+        println!("{} strings: {}", name, tally.nice);
     }
-    println!("Nice strings: {}", count);
 
     // Light grid stuff
     let input = fs::read_to_string("data/day6.txt").unwrap();
+    let instructions = lights::parse(&input).unwrap();
+
     let mut grid = Grid::new(1000, 1000);
-    grid.process(&input);
+    grid.process(&instructions, &Boolean).unwrap();
     println!("lights on: {}", grid.count());
 
+    let mut grid = Grid::new(1000, 1000);
+    grid.process(&instructions, &Brightness).unwrap();
+    println!("total brightness: {}", grid.count());
+
     // Circuit stuff
     let input = fs::read_to_string("data/day7.txt").unwrap();
     let mut circuit = Circuit::new();
@@ -114,10 +106,7 @@ fn main() {
     let a = circuit.get_wire("a".to_string());
     println!("a: {}", a);
 
-    circuit.reset();
     circuit.set_wire("b".to_string(), a);
-    circuit.assemble(input.trim().lines().collect());
-
     let a = circuit.get_wire("a".to_string());
     println!("a2: {}", a);
 
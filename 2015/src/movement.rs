@@ -0,0 +1,318 @@
+use std::collections::HashSet;
+use std::fmt::Display;
+use std::num::ParseIntError;
+
+use crate::grid::Position;
+
+/// Unit moves in the `Position` convention: `^` (north) increases `x` and
+/// `>` (east) increases `y`.
+const NORTH: (i64, i64) = (1, 0);
+const SOUTH: (i64, i64) = (-1, 0);
+const EAST: (i64, i64) = (0, 1);
+const WEST: (i64, i64) = (0, -1);
+
+/// Longest single move we accept. Routes record every unit step, so a
+/// bigger count would mostly be a way of running out of memory.
+pub const MAX_COUNT: u64 = 1_000_000;
+
+/// Flavours of route files we know how to read.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Dialect {
+    /// One `^`, `v`, `<` or `>` per step, e.g. `^>v<`.
+    Arrows,
+    /// Arrows followed by an optional repeat count, e.g. `>5^<3`.
+    RunLength,
+    /// Compass points, diagonals included, each with an optional count and
+    /// separated by commas or spaces, e.g. `N3, NE, W2`.
+    Compass,
+    /// Turn left or right then walk, starting north, e.g. `R2, L3`.
+    Relative,
+}
+
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub enum Move {
+    Walk { dx: i64, dy: i64, steps: i64 },
+    Turn { right: bool, steps: i64 },
+}
+
+#[derive(Debug, PartialEq)]
+pub enum MoveError {
+    InvalidToken(String),
+    InvalidCount(ParseIntError),
+    /// Count above `MAX_COUNT`.
+    CountTooLarge(u64),
+}
+
+impl Display for MoveError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            MoveError::InvalidToken(token) => write!(f, "invalid instruction: {}", token),
+            MoveError::InvalidCount(parse_int_error) => write!(f, "{}", parse_int_error),
+            MoveError::CountTooLarge(count) => {
+                write!(f, "count {} is above the limit of {}", count, MAX_COUNT)
+            }
+        }
+    }
+}
+
+impl From<ParseIntError> for MoveError {
+    fn from(e: ParseIntError) -> Self {
+        MoveError::InvalidCount(e)
+    }
+}
+
+fn arrow(c: char) -> Option<(i64, i64)> {
+    match c {
+        '^' => Some(NORTH),
+        'v' => Some(SOUTH),
+        '>' => Some(EAST),
+        '<' => Some(WEST),
+        _ => None,
+    }
+}
+
+fn compass(point: &str) -> Option<(i64, i64)> {
+    let mut direction = (0, 0);
+
+    for c in point.chars() {
+        let (dx, dy) = match c {
+            'N' => NORTH,
+            'S' => SOUTH,
+            'E' => EAST,
+            'W' => WEST,
+            _ => return None,
+        };
+        direction = (direction.0 + dx, direction.1 + dy);
+    }
+
+    match (point.len(), direction) {
+        (1, _) => Some(direction),
+        (2, (dx, dy)) if dx != 0 && dy != 0 => Some(direction),
+        _ => None,
+    }
+}
+
+/// Repeat count of a move. Counts are unsigned, so `-5` is rejected.
+fn parse_count(digits: &str) -> Result<i64, MoveError> {
+    let count = digits.parse::<u64>()?;
+    if count > MAX_COUNT {
+        return Err(MoveError::CountTooLarge(count));
+    }
+    Ok(count as i64)
+}
+
+fn parse_optional_count(digits: &str) -> Result<i64, MoveError> {
+    if digits.is_empty() {
+        Ok(1)
+    } else {
+        parse_count(digits)
+    }
+}
+
+fn parse_arrows(input: &str, counted: bool) -> Result<Vec<Move>, MoveError> {
+    let mut moves = Vec::new();
+    let mut chars = input.chars().filter(|c| !c.is_whitespace()).peekable();
+
+    while let Some(c) = chars.next() {
+        let (dx, dy) = arrow(c).ok_or(MoveError::InvalidToken(c.to_string()))?;
+
+        let mut digits = String::new();
+        while counted && chars.peek().is_some_and(|c| c.is_ascii_digit()) {
+            digits.push(chars.next().unwrap());
+        }
+
+        let steps = parse_optional_count(&digits)?;
+        moves.push(Move::Walk { dx, dy, steps });
+    }
+    Ok(moves)
+}
+
+fn tokens(input: &str) -> impl Iterator<Item = &str> {
+    input
+        .split(|c: char| c == ',' || c.is_whitespace())
+        .filter(|t| !t.is_empty())
+}
+
+fn parse_compass(input: &str) -> Result<Vec<Move>, MoveError> {
+    let mut moves = Vec::new();
+
+    for token in tokens(input) {
+        let split = token
+            .find(|c: char| c.is_ascii_digit())
+            .unwrap_or(token.len());
+        let (point, digits) = token.split_at(split);
+
+        let (dx, dy) = compass(point).ok_or(MoveError::InvalidToken(token.to_string()))?;
+        let steps = parse_optional_count(digits)?;
+        moves.push(Move::Walk { dx, dy, steps });
+    }
+    Ok(moves)
+}
+
+fn parse_relative(input: &str) -> Result<Vec<Move>, MoveError> {
+    let mut moves = Vec::new();
+
+    for token in tokens(input) {
+        let right = match token.as_bytes()[0] {
+            b'R' => true,
+            b'L' => false,
+            _ => return Err(MoveError::InvalidToken(token.to_string())),
+        };
+
+        let steps = parse_count(&token[1..])?;
+        moves.push(Move::Turn { right, steps });
+    }
+    Ok(moves)
+}
+
+pub fn parse(input: &str, dialect: Dialect) -> Result<Vec<Move>, MoveError> {
+    match dialect {
+        Dialect::Arrows => parse_arrows(input, false),
+        Dialect::RunLength => parse_arrows(input, true),
+        Dialect::Compass => parse_compass(input),
+        Dialect::Relative => parse_relative(input),
+    }
+}
+
+/// Interprets moves, keeping track of where we stand and which way we face.
+pub struct Walker {
+    position: Position,
+    heading: (i64, i64),
+}
+
+impl Walker {
+    pub fn new(position: Position) -> Walker {
+        Walker {
+            position,
+            heading: NORTH,
+        }
+    }
+
+    pub fn position(&self) -> Position {
+        self.position
+    }
+
+    /// Applies a single move, returning its direction and length.
+    pub fn apply(&mut self, m: &Move) -> ((i64, i64), i64) {
+        let (direction, steps) = match *m {
+            Move::Walk { dx, dy, steps } => ((dx, dy), steps),
+            Move::Turn { right, steps } => {
+                let (dx, dy) = self.heading;
+                self.heading = if right { (-dy, dx) } else { (dy, -dx) };
+                (self.heading, steps)
+            }
+        };
+
+        self.position = self.position.offset(direction.0, direction.1, steps);
+        (direction, steps)
+    }
+
+    /// Every house stood on while following `moves`, starting position
+    /// included, one entry per unit step.
+    pub fn route(&mut self, moves: &[Move]) -> Vec<Position> {
+        let mut route = vec![self.position];
+
+        for m in moves {
+            let start = self.position;
+            let ((dx, dy), steps) = self.apply(m);
+
+            for step in 1..=steps {
+                route.push(start.offset(dx, dy, step));
+            }
+        }
+        route
+    }
+}
+
+/// Set of houses visited by following `input` from the origin.
+pub fn visited(input: &str, dialect: Dialect) -> Result<HashSet<Position>, MoveError> {
+    let moves = parse(input, dialect)?;
+    let mut walker = Walker::new(Position::new(0, 0));
+
+    Ok(walker.route(&moves).into_iter().collect())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_arrows() {
+        assert_eq!(2, visited(">", Dialect::Arrows).unwrap().len());
+        assert_eq!(4, visited("^>v<", Dialect::Arrows).unwrap().len());
+        assert_eq!(2, visited("^v^v^v^v^v", Dialect::Arrows).unwrap().len());
+        assert_eq!(
+            Err(MoveError::InvalidToken("5".to_string())),
+            visited(">5", Dialect::Arrows)
+        );
+    }
+
+    #[test]
+    fn test_dialects_agree() {
+        let expected = visited(">>>^^<v", Dialect::Arrows).unwrap();
+
+        assert_eq!(expected, visited(">3^2<v", Dialect::RunLength).unwrap());
+        assert_eq!(expected, visited("E3, N2 W S", Dialect::Compass).unwrap());
+        assert_eq!(
+            expected,
+            visited("R3, L2, L1, L1", Dialect::Relative).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_diagonals() {
+        let houses = visited("NE2, SW", Dialect::Compass).unwrap();
+        let expected = HashSet::from([
+            Position::new(0, 0),
+            Position::new(1, 1),
+            Position::new(2, 2),
+        ]);
+        assert_eq!(expected, houses);
+
+        assert!(visited("NS", Dialect::Compass).is_err());
+        assert!(visited("NNE", Dialect::Compass).is_err());
+    }
+
+    #[test]
+    fn test_relative() {
+        let moves = parse("R2, L3", Dialect::Relative).unwrap();
+        let mut walker = Walker::new(Position::new(0, 0));
+        walker.route(&moves);
+        assert_eq!(Position::new(3, 2), walker.position());
+
+        let moves = parse("R5, L5, R5, R3", Dialect::Relative).unwrap();
+        let mut walker = Walker::new(Position::new(0, 0));
+        walker.route(&moves);
+        assert_eq!(Position::new(2, 10), walker.position());
+
+        assert!(parse("X2", Dialect::Relative).is_err());
+        assert!(parse("R", Dialect::Relative).is_err());
+    }
+
+    #[test]
+    fn test_counts() {
+        assert!(matches!(
+            parse("R-5", Dialect::Relative),
+            Err(MoveError::InvalidCount(_))
+        ));
+        assert_eq!(
+            Err(MoveError::CountTooLarge(99999999999999999)),
+            parse("L99999999999999999", Dialect::Relative)
+        );
+        assert!(matches!(
+            parse("R999999999999999999999", Dialect::Relative),
+            Err(MoveError::InvalidCount(_))
+        ));
+        assert_eq!(
+            Err(MoveError::CountTooLarge(MAX_COUNT + 1)),
+            parse(&format!(">{}", MAX_COUNT + 1), Dialect::RunLength)
+        );
+        assert_eq!(
+            Ok(vec![Move::Turn {
+                right: true,
+                steps: MAX_COUNT as i64
+            }]),
+            parse(&format!("R{}", MAX_COUNT), Dialect::Relative)
+        );
+    }
+}
@@ -1,37 +1,250 @@
+use std::any::Any;
+use std::collections::HashMap;
+
 #[derive(PartialEq, Eq, Debug)]
 pub enum Value {
     Nice,
     Naughty,
 }
 
-fn validate_pairs(input: &str) -> bool {
-    for (i, _) in input[..input.len() - 2].chars().enumerate() {
-        let letters = &input[i..i + 2];
+/// Which of the two lists of rules a string is checked against.
+#[derive(PartialEq, Eq, Debug, Clone, Copy)]
+pub enum RuleSet {
+    /// At least three vowels, a doubled letter and none of `ab`, `cd`,
+    /// `pq` or `xy`.
+    Original,
+    /// A pair appearing twice without overlapping and a letter repeated
+    /// with exactly one letter in between.
+    Revised,
+}
+
+const FORBIDDEN_PAIRS: [&str; 4] = ["ab", "cd", "pq", "xy"];
+
+/// Bytes counted by `Vowels`.
+pub const VOWELS: &[u8] = b"aeiou";
+
+/// Result of evaluating a single rule. `indices` points at the bytes that
+/// made the rule match, or fail for rules like `Forbid`.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Outcome {
+    pub rule: String,
+    pub passed: bool,
+    pub indices: Vec<usize>,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub struct Verdict {
+    pub value: Value,
+    pub outcomes: Vec<Outcome>,
+}
+
+impl Verdict {
+    pub fn failed(&self) -> impl Iterator<Item = &Outcome> {
+        self.outcomes.iter().filter(|o| !o.passed)
+    }
+}
+
+/// `Any` lets callers tell the built-in rules apart, the scanner relying on
+/// it to turn them into byte-at-a-time matchers.
+pub trait Rule: Any + Send + Sync {
+    fn name(&self) -> String;
+
+    /// Evaluates the rule against `input`, appending its outcome followed by
+    /// the outcomes of every rule nested in it.
+    fn evaluate(&self, input: &[u8], outcomes: &mut Vec<Outcome>) -> bool;
+}
+
+fn leaf(outcomes: &mut Vec<Outcome>, rule: String, passed: bool, indices: Vec<usize>) -> bool {
+    outcomes.push(Outcome {
+        rule,
+        passed,
+        indices,
+    });
+    passed
+}
+
+/// At least that many vowels.
+pub struct Vowels(pub usize);
+
+impl Rule for Vowels {
+    fn name(&self) -> String {
+        format!("vowels >= {}", self.0)
+    }
+
+    fn evaluate(&self, input: &[u8], outcomes: &mut Vec<Outcome>) -> bool {
+        let indices: Vec<usize> = input
+            .iter()
+            .enumerate()
+            .filter(|(_, c)| VOWELS.contains(c))
+            .map(|(i, _)| i)
+            .collect();
+
+        leaf(outcomes, self.name(), indices.len() >= self.0, indices)
+    }
+}
+
+/// A run of `width` bytes showing up again `gap` bytes after it ends, or
+/// anywhere after it ends when `gap` is `None`. Indices are the start of the
+/// first copy.
+pub struct Repeat {
+    pub width: usize,
+    pub gap: Option<usize>,
+}
+
+impl Rule for Repeat {
+    fn name(&self) -> String {
+        match self.gap {
+            Some(gap) => format!("repeat({}) gap {}", self.width, gap),
+            None => format!("repeat({}) non-overlapping", self.width),
+        }
+    }
+
+    fn evaluate(&self, input: &[u8], outcomes: &mut Vec<Outcome>) -> bool {
+        let w = self.width.max(1);
+        let mut indices = Vec::new();
+
+        match self.gap {
+            Some(gap) => {
+                for i in 0..input.len().saturating_sub(2 * w + gap - 1) {
+                    if input[i..i + w] == input[i + w + gap..i + 2 * w + gap] {
+                        indices.push(i);
+                    }
+                }
+            }
+            None => {
+                let mut first: HashMap<&[u8], (usize, bool)> = HashMap::new();
 
-        if input[i + 2..].contains(letters) {
-            return true;
+                for j in 0..input.len().saturating_sub(w - 1) {
+                    let (i, found) = first.entry(&input[j..j + w]).or_insert((j, false));
+                    if !*found && *i + w <= j {
+                        *found = true;
+                        indices.push(*i);
+                    }
+                }
+                indices.sort_unstable();
+            }
         }
+
+        leaf(outcomes, self.name(), !indices.is_empty(), indices)
     }
-    false
 }
 
-fn validate_palindrome(input: &str) -> bool {
-    for (i, c) in input[..input.len() - 2].chars().enumerate() {
-        if c == input.as_bytes()[i + 2] as char {
-            return true;
+/// None of the given substrings.
+pub struct Forbid(pub Vec<Vec<u8>>);
+
+impl Rule for Forbid {
+    fn name(&self) -> String {
+        let words: Vec<String> = self
+            .0
+            .iter()
+            .map(|w| format!("{:?}", String::from_utf8_lossy(w)))
+            .collect();
+        format!("forbid {}", words.join(","))
+    }
+
+    fn evaluate(&self, input: &[u8], outcomes: &mut Vec<Outcome>) -> bool {
+        let mut indices = Vec::new();
+
+        for i in 0..input.len() {
+            if self.0.iter().any(|w| input[i..].starts_with(w)) {
+                indices.push(i);
+            }
         }
+
+        leaf(outcomes, self.name(), indices.is_empty(), indices)
     }
-    false
 }
 
-pub fn validate(input: &str) -> Value {
-    let pairs = validate_pairs(input);
-    let palindrome = validate_palindrome(input);
+/// Combines nested rules, `Not` inverting a single one.
+pub enum Combinator {
+    All(Vec<Box<dyn Rule>>),
+    Any(Vec<Box<dyn Rule>>),
+    Not(Box<dyn Rule>),
+}
 
-    if pairs && palindrome {
-        return Value::Nice;
+impl Rule for Combinator {
+    fn name(&self) -> String {
+        match self {
+            Combinator::All(_) => "all".to_string(),
+            Combinator::Any(_) => "any".to_string(),
+            Combinator::Not(rule) => format!("not {}", rule.name()),
+        }
     }
-    Value::Naughty
+
+    fn evaluate(&self, input: &[u8], outcomes: &mut Vec<Outcome>) -> bool {
+        let slot = outcomes.len();
+        leaf(outcomes, self.name(), false, Vec::new());
+
+        // Every nested rule is evaluated, even once the result is known,
+        // so the verdict lists all of them.
+        let passed = match self {
+            Combinator::All(rules) => {
+                let mut passed = true;
+                for rule in rules {
+                    passed &= rule.evaluate(input, outcomes);
+                }
+                passed
+            }
+            Combinator::Any(rules) => {
+                let mut passed = false;
+                for rule in rules {
+                    passed |= rule.evaluate(input, outcomes);
+                }
+                passed
+            }
+            Combinator::Not(rule) => !rule.evaluate(input, outcomes),
+        };
+
+        outcomes[slot].passed = passed;
+        passed
+    }
+}
+
+impl RuleSet {
+    pub fn rule(&self) -> Box<dyn Rule> {
+        let rules: Vec<Box<dyn Rule>> = match self {
+            RuleSet::Original => vec![
+                Box::new(Vowels(3)),
+                Box::new(Repeat {
+                    width: 1,
+                    gap: Some(0),
+                }),
+                Box::new(Forbid(
+                    FORBIDDEN_PAIRS
+                        .iter()
+                        .map(|p| p.as_bytes().to_vec())
+                        .collect(),
+                )),
+            ],
+            RuleSet::Revised => vec![
+                Box::new(Repeat {
+                    width: 2,
+                    gap: None,
+                }),
+                Box::new(Repeat {
+                    width: 1,
+                    gap: Some(1),
+                }),
+            ],
+        };
+
+        Box::new(Combinator::All(rules))
+    }
+}
+
+pub fn check(input: &[u8], rule: &dyn Rule) -> Verdict {
+    let mut outcomes = Vec::new();
+    let value = if rule.evaluate(input, &mut outcomes) {
+        Value::Nice
+    } else {
+        Value::Naughty
+    };
+
+    Verdict { value, outcomes }
+}
+
+pub fn validate(input: &str, rules: RuleSet) -> Value {
+    check(input.as_bytes(), rules.rule().as_ref()).value
 }
 
 #[cfg(test)]
@@ -41,19 +254,92 @@ mod tests {
     #[test]
     fn test_validation() {
         let input = "qjhvhtzxzqqjkmpb";
-        let result = validate(input);
+        let result = validate(input, RuleSet::Revised);
         assert_eq!(Value::Nice, result);
 
         let input = "xxyxx";
-        let result = validate(input);
+        let result = validate(input, RuleSet::Revised);
         assert_eq!(Value::Nice, result);
 
         let input = "uurcxstgmygtbstg";
-        let result = validate(input);
+        let result = validate(input, RuleSet::Revised);
         assert_eq!(Value::Naughty, result);
 
         let input = "ieodomkazucvgmuy";
-        let result = validate(input);
+        let result = validate(input, RuleSet::Revised);
         assert_eq!(Value::Naughty, result);
     }
+
+    #[test]
+    fn test_original_validation() {
+        let input = "ugknbfddgicrmopn";
+        let result = validate(input, RuleSet::Original);
+        assert_eq!(Value::Nice, result);
+
+        let input = "aaa";
+        let result = validate(input, RuleSet::Original);
+        assert_eq!(Value::Nice, result);
+
+        let input = "jchzalrnumimnmhp";
+        let result = validate(input, RuleSet::Original);
+        assert_eq!(Value::Naughty, result);
+
+        let input = "haegwjzuvuyypxyu";
+        let result = validate(input, RuleSet::Original);
+        assert_eq!(Value::Naughty, result);
+
+        let input = "dvszwmarrgswjxmb";
+        let result = validate(input, RuleSet::Original);
+        assert_eq!(Value::Naughty, result);
+    }
+
+    #[test]
+    fn test_short_strings() {
+        for input in ["", "a", "aa", "ab", "aba"] {
+            assert_eq!(Value::Naughty, validate(input, RuleSet::Revised));
+            assert_eq!(Value::Naughty, validate(input, RuleSet::Original));
+        }
+        assert_eq!(Value::Nice, validate("aaaa", RuleSet::Revised));
+        assert_eq!(Value::Naughty, validate("aaa", RuleSet::Revised));
+    }
+
+    #[test]
+    fn test_verdict() {
+        let verdict = check(b"haegwjzuvuyypxyu", RuleSet::Original.rule().as_ref());
+        assert_eq!(Value::Naughty, verdict.value);
+
+        let failed: Vec<&Outcome> = verdict.failed().collect();
+        assert_eq!(2, failed.len());
+        assert_eq!("all", failed[0].rule);
+        assert_eq!("forbid \"ab\",\"cd\",\"pq\",\"xy\"", failed[1].rule);
+        assert_eq!(vec![13], failed[1].indices);
+
+        let verdict = check(b"qjhvhtzxzqqjkmpb", RuleSet::Revised.rule().as_ref());
+        assert_eq!(Value::Nice, verdict.value);
+        assert_eq!(vec![0], verdict.outcomes[1].indices);
+        assert_eq!(vec![2, 6], verdict.outcomes[2].indices);
+    }
+
+    #[test]
+    fn test_combinators() {
+        let rule = Combinator::Any(vec![
+            Box::new(Vowels(2)),
+            Box::new(Combinator::Not(Box::new(Forbid(vec![b"z".to_vec()])))),
+        ]);
+
+        let verdict = check(b"xyz", &rule);
+        assert_eq!(Value::Nice, verdict.value);
+        assert_eq!(
+            vec!["any", "vowels >= 2", "not forbid \"z\"", "forbid \"z\""],
+            verdict
+                .outcomes
+                .iter()
+                .map(|o| o.rule.as_str())
+                .collect::<Vec<&str>>()
+        );
+        assert_eq!(vec![2], verdict.outcomes[3].indices);
+
+        assert_eq!(Value::Naughty, check(b"abc", &rule).value);
+        assert_eq!(Value::Nice, check(b"aeb", &rule).value);
+    }
 }
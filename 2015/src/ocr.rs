@@ -0,0 +1,339 @@
+use std::fmt::Display;
+
+use crate::lights::Grid;
+
+type Font = [(char, &'static [&'static str])];
+
+/// Letters 6 lights tall, mostly 4 wide, in cells 5 lights wide.
+const SMALL: &Font = &[
+    ('A', &[".##.", "#..#", "#..#", "####", "#..#", "#..#"]),
+    ('B', &["###.", "#..#", "###.", "#..#", "#..#", "###."]),
+    ('C', &[".##.", "#..#", "#...", "#...", "#..#", ".##."]),
+    ('E', &["####", "#...", "###.", "#...", "#...", "####"]),
+    ('F', &["####", "#...", "###.", "#...", "#...", "#..."]),
+    ('G', &[".##.", "#..#", "#...", "#.##", "#..#", ".###"]),
+    ('H', &["#..#", "#..#", "####", "#..#", "#..#", "#..#"]),
+    ('I', &["###", ".#.", ".#.", ".#.", ".#.", "###"]),
+    ('J', &["..##", "...#", "...#", "...#", "#..#", ".##."]),
+    ('K', &["#..#", "#.#.", "##..", "#.#.", "#.#.", "#..#"]),
+    ('L', &["#...", "#...", "#...", "#...", "#...", "####"]),
+    ('O', &[".##.", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('P', &["###.", "#..#", "#..#", "###.", "#...", "#..."]),
+    ('R', &["###.", "#..#", "#..#", "###.", "#.#.", "#..#"]),
+    ('S', &[".###", "#...", "#...", ".##.", "...#", "###."]),
+    ('U', &["#..#", "#..#", "#..#", "#..#", "#..#", ".##."]),
+    ('Y', &["#...#", "#...#", ".#.#.", "..#..", "..#..", "..#.."]),
+    ('Z', &["####", "...#", "..#.", ".#..", "#...", "####"]),
+];
+
+/// Letters 10 lights tall and 6 wide, in cells 8 lights wide.
+const LARGE: &Font = &[
+    (
+        'A',
+        &[
+            "..##..", ".#..#.", "#....#", "#....#", "#....#", "######", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'B',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#....#", "#....#", "#....#",
+            "#....#", "#####.",
+        ],
+    ),
+    (
+        'C',
+        &[
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#....#", ".####.",
+        ],
+    ),
+    (
+        'E',
+        &[
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'F',
+        &[
+            "######", "#.....", "#.....", "#.....", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'G',
+        &[
+            ".####.", "#....#", "#.....", "#.....", "#.....", "#..###", "#....#", "#....#",
+            "#...##", ".###.#",
+        ],
+    ),
+    (
+        'H',
+        &[
+            "#....#", "#....#", "#....#", "#....#", "######", "#....#", "#....#", "#....#",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'J',
+        &[
+            "...###", "....#.", "....#.", "....#.", "....#.", "....#.", "....#.", "#...#.",
+            "#...#.", ".###..",
+        ],
+    ),
+    (
+        'K',
+        &[
+            "#....#", "#...#.", "#..#..", "#.#...", "##....", "##....", "#.#...", "#..#..",
+            "#...#.", "#....#",
+        ],
+    ),
+    (
+        'L',
+        &[
+            "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+    (
+        'N',
+        &[
+            "#....#", "##...#", "##...#", "#.#..#", "#.#..#", "#..#.#", "#..#.#", "#...##",
+            "#...##", "#....#",
+        ],
+    ),
+    (
+        'P',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#.....", "#.....", "#.....",
+            "#.....", "#.....",
+        ],
+    ),
+    (
+        'R',
+        &[
+            "#####.", "#....#", "#....#", "#....#", "#####.", "#..#..", "#...#.", "#...#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'X',
+        &[
+            "#....#", "#....#", ".#..#.", ".#..#.", "..##..", "..##..", ".#..#.", ".#..#.",
+            "#....#", "#....#",
+        ],
+    ),
+    (
+        'Z',
+        &[
+            "######", ".....#", ".....#", "....#.", "...#..", "..#...", ".#....", "#.....",
+            "#.....", "######",
+        ],
+    ),
+];
+
+/// Glyph that matched no letter, drawn with `#` and `.`.
+#[derive(PartialEq, Eq, Debug)]
+pub struct Glyph {
+    pub column: usize,
+    pub pattern: String,
+}
+
+#[derive(PartialEq, Eq, Debug)]
+pub enum OcrError {
+    /// Lit rows don't add up to the height of any font.
+    Height(usize),
+    /// Text read so far, `?` standing in for each unknown glyph.
+    Unknown { text: String, glyphs: Vec<Glyph> },
+}
+
+impl Display for OcrError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            OcrError::Height(h) => write!(f, "no font is {} lights tall", h),
+            OcrError::Unknown { text, glyphs } => {
+                let columns: Vec<String> = glyphs.iter().map(|g| g.column.to_string()).collect();
+                write!(
+                    f,
+                    "unknown glyphs at columns {} in '{}'",
+                    columns.join(", "),
+                    text
+                )
+            }
+        }
+    }
+}
+
+/// Reads capital letters off rows of lights. Blank rows around the text are
+/// ignored and letters are told apart by the blank columns between them, or
+/// by the width of their cells for letters like `Y` that fill their cell.
+pub fn read(rows: &[Vec<bool>]) -> Result<String, OcrError> {
+    let lit: Vec<usize> = (0..rows.len())
+        .filter(|y| rows[*y].iter().any(|on| *on))
+        .collect();
+    let rows = match (lit.first(), lit.last()) {
+        (Some(top), Some(bottom)) => &rows[*top..=*bottom],
+        _ => return Ok(String::new()),
+    };
+
+    let (font, pitch) = match rows.len() {
+        6 => (SMALL, 5),
+        10 => (LARGE, 8),
+        h => return Err(OcrError::Height(h)),
+    };
+    let widest = font.iter().map(|(_, g)| g[0].len()).max().unwrap_or(0);
+
+    let width = rows.iter().map(|row| row.len()).max().unwrap_or(0);
+    let on = |x: usize, y: usize| rows[y].get(x).copied().unwrap_or(false);
+    let blank = |x: usize| (0..rows.len()).all(|y| !on(x, y));
+
+    let mut text = String::new();
+    let mut glyphs = Vec::new();
+    let mut x = 0;
+
+    while x < width {
+        if blank(x) {
+            x += 1;
+            continue;
+        }
+
+        let run = x;
+        while x < width && !blank(x) {
+            x += 1;
+        }
+
+        // Too wide for one letter, so letters touch and are cut by cell.
+        let step = if x - run > widest { pitch } else { x - run };
+
+        for start in (run..x).step_by(step) {
+            let end = (start + step).min(x);
+            let pattern: Vec<String> = (0..rows.len())
+                .map(|y| {
+                    (start..end)
+                        .map(|cx| if on(cx, y) { '#' } else { '.' })
+                        .collect()
+                })
+                .collect();
+
+            match font.iter().find(|(_, g)| g.iter().eq(pattern.iter())) {
+                Some((c, _)) => text.push(*c),
+                None => {
+                    text.push('?');
+                    glyphs.push(Glyph {
+                        column: start,
+                        pattern: pattern.join("\n"),
+                    });
+                }
+            }
+        }
+    }
+
+    if glyphs.is_empty() {
+        Ok(text)
+    } else {
+        Err(OcrError::Unknown { text, glyphs })
+    }
+}
+
+/// Same as `read`, any light that isn't off counting as lit.
+pub fn read_grid(grid: &Grid) -> Result<String, OcrError> {
+    let rows: Vec<Vec<bool>> = (0..grid.height())
+        .map(|y| (0..grid.width()).map(|x| grid.get(x, y) > 0).collect())
+        .collect();
+
+    read(&rows)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::lights::{parse, Boolean};
+
+    /// Lays glyphs out left to right, each at the start of a cell `pitch`
+    /// columns wide.
+    fn render(font: &Font, text: &str, pitch: usize) -> Vec<Vec<bool>> {
+        let height = font[0].1.len();
+        let mut rows = vec![Vec::new(); height];
+
+        for c in text.chars() {
+            let (_, glyph) = font.iter().find(|(l, _)| *l == c).unwrap();
+            for (row, line) in rows.iter_mut().zip(glyph.iter()) {
+                row.extend(line.chars().map(|p| p == '#'));
+                row.resize(row.len() + pitch - line.len(), false);
+            }
+        }
+        rows
+    }
+
+    #[test]
+    fn test_fonts() {
+        let small = "ABCEFGHIJKLOPRSUYZ";
+        assert_eq!(small, read(&render(SMALL, small, 5)).unwrap());
+
+        let large = "ABCEFGHJKLNPRXZ";
+        let mut rows = render(LARGE, large, 8);
+        rows.insert(0, vec![false; 3]);
+        rows.push(Vec::new());
+        assert_eq!(large, read(&rows).unwrap());
+
+        assert_eq!("", read(&[vec![false; 5]]).unwrap());
+    }
+
+    #[test]
+    fn test_touching() {
+        // `Y` fills its cell, leaving no blank column before the next letter.
+        for text in ["YA", "AY", "YYY", "YIA", "HYZY"] {
+            assert_eq!(text, read(&render(SMALL, text, 5)).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_grid() {
+        let input = [
+            "rect 1x6",
+            "turn on 0,5 through 3,5",
+            "turn on 6,0 through 8,0",
+            "turn on 7,1 through 7,5",
+            "turn on 6,5 through 8,5",
+        ];
+        let mut grid = Grid::new(12, 8);
+        grid.process(&parse(&input.join("\n")).unwrap(), &Boolean)
+            .unwrap();
+        assert_eq!("LI", read_grid(&grid).unwrap());
+
+        // Joined by their bottom rows, they're cut into cells that don't
+        // hold letters anymore.
+        grid.process(
+            &parse("rect 7x6\nturn off 0,0 through 6,4").unwrap(),
+            &Boolean,
+        )
+        .unwrap();
+        assert!(matches!(
+            read_grid(&grid),
+            Err(OcrError::Unknown { glyphs, .. }) if glyphs.len() == 2
+        ));
+    }
+
+    #[test]
+    fn test_unknown() {
+        let mut rows = render(SMALL, "HI", 5);
+        rows[0][1] = true;
+
+        let error = read(&rows).unwrap_err();
+        assert_eq!("unknown glyphs at columns 0 in '?I'", error.to_string());
+        assert_eq!(
+            OcrError::Unknown {
+                text: "?I".to_string(),
+                glyphs: vec![Glyph {
+                    column: 0,
+                    pattern: "##.#\n#..#\n####\n#..#\n#..#\n#..#".to_string()
+                }]
+            },
+            error
+        );
+        assert_eq!(OcrError::Height(3), read(&rows[..3]).unwrap_err());
+    }
+}
@@ -1,5 +1,5 @@
 const PASSWORD_LENGTH: usize = 8;
-const FORBIDDEN_LETTERS: &[u8] = &[b'i', b'o', b'l'];
+const FORBIDDEN_LETTERS: &[u8] = b"iol";
 
 fn check_straight(input: &[u8]) -> bool {
     for (i, c) in input.iter().enumerate() {
@@ -105,7 +105,7 @@ pub fn find_shortest_path(input: &str) -> usize {
         };
     }
 
-    let mut min = std::usize::MAX;
+    let mut min = usize::MAX;
     for (key, node) in nodes.iter() {
         let mut visited: HashSet<String> = HashSet::new();
         visited.insert(key.to_string());
@@ -134,7 +134,7 @@ pub fn find_longest_path(input: &str) -> usize {
         };
     }
 
-    let mut max = std::usize::MIN;
+    let mut max = usize::MIN;
     for (key, node) in nodes.iter() {
         let mut visited: HashSet<String> = HashSet::new();
         visited.insert(key.to_string());
@@ -0,0 +1,301 @@
+use std::collections::HashMap;
+
+use crate::grid::Position;
+use crate::movement::{Move, Walker};
+
+/// A house stood on again, `step` being the index in the route of the
+/// repeated visit and `first_step` the index of the original one.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Revisit {
+    pub position: Position,
+    pub step: usize,
+    pub first_step: usize,
+}
+
+/// Every repeated visit along a route of unit steps, in route order.
+pub fn revisits(route: &[Position]) -> Vec<Revisit> {
+    let mut seen: HashMap<Position, usize> = HashMap::new();
+    let mut output = Vec::new();
+
+    for (step, position) in route.iter().enumerate() {
+        match seen.get(position) {
+            Some(first_step) => output.push(Revisit {
+                position: *position,
+                step,
+                first_step: *first_step,
+            }),
+            None => {
+                seen.insert(*position, step);
+            }
+        }
+    }
+    output
+}
+
+pub fn first_revisit(route: &[Position]) -> Option<Revisit> {
+    let mut seen: HashMap<Position, usize> = HashMap::new();
+
+    for (step, position) in route.iter().enumerate() {
+        if let Some(first_step) = seen.insert(*position, step) {
+            return Some(Revisit {
+                position: *position,
+                step,
+                first_step,
+            });
+        }
+    }
+    None
+}
+
+/// A straight run of `length` unit steps in `direction`. `step` is the
+/// route index of `start`.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Segment {
+    pub start: Position,
+    pub direction: (i64, i64),
+    pub length: i64,
+    pub step: usize,
+}
+
+impl Segment {
+    pub fn end(&self) -> Position {
+        self.point(self.length)
+    }
+
+    fn point(&self, s: i64) -> Position {
+        self.start.offset(self.direction.0, self.direction.1, s)
+    }
+
+    fn x_range(&self) -> (i64, i64) {
+        let (a, b) = (self.start.x(), self.end().x());
+        (a.min(b), a.max(b))
+    }
+
+    fn y_range(&self) -> (i64, i64) {
+        let (a, b) = (self.start.y(), self.end().y());
+        (a.min(b), a.max(b))
+    }
+}
+
+/// Splits the route described by `moves` into straight segments. Moves of
+/// length zero are dropped, they can't cross anything.
+pub fn segments(start: Position, moves: &[Move]) -> Vec<Segment> {
+    let mut walker = Walker::new(start);
+    let mut step = 0;
+    let mut output = Vec::new();
+
+    for m in moves {
+        let start = walker.position();
+        let (direction, length) = walker.apply(m);
+
+        if length > 0 {
+            output.push(Segment {
+                start,
+                direction,
+                length,
+                step,
+            });
+            step += length as usize;
+        }
+    }
+    output
+}
+
+/// A point where segment `segments.1` runs into the earlier segment
+/// `segments.0`, `step` being the route index of the point.
+#[derive(Debug, PartialEq, Clone, Copy)]
+pub struct Crossing {
+    pub position: Position,
+    pub step: usize,
+    pub segments: (usize, usize),
+}
+
+fn cross(u: (i64, i64), v: (i64, i64)) -> i64 {
+    u.0 * v.1 - u.1 * v.0
+}
+
+/// Bounds of the `s` in `[s_min, later.length]` for which `later.point(s)`
+/// lies on `earlier`, both included. Directions are unit steps, diagonals
+/// included, so parallel segments only ever run the same or the opposite
+/// way and overlap along a single stretch.
+fn meet(earlier: &Segment, later: &Segment, s_min: i64) -> Option<(i64, i64)> {
+    let d = earlier.direction;
+    let e = later.direction;
+    let w = (
+        later.start.x() - earlier.start.x(),
+        later.start.y() - earlier.start.y(),
+    );
+
+    let den = cross(d, e);
+    if den != 0 {
+        let t = cross(w, e);
+        let s = cross(w, d);
+
+        if t % den != 0 || s % den != 0 {
+            return None;
+        }
+
+        let (t, s) = (t / den, s / den);
+        if (0..=earlier.length).contains(&t) && (s_min..=later.length).contains(&s) {
+            return Some((s, s));
+        }
+        return None;
+    }
+
+    if cross(w, d) != 0 {
+        return None;
+    }
+
+    let t0 = if d.0 != 0 { w.0 / d.0 } else { w.1 / d.1 };
+    let (lo, hi) = if e == d {
+        (-t0, earlier.length - t0)
+    } else {
+        (t0 - earlier.length, t0)
+    };
+
+    let (lo, hi) = (lo.max(s_min), hi.min(later.length));
+    if lo <= hi {
+        Some((lo, hi))
+    } else {
+        None
+    }
+}
+
+/// Every point where a segment runs into an earlier one, ordered by step,
+/// so the steps are exactly those of `revisits` on the same route. Points
+/// lying on several earlier segments are reported once, against the first
+/// of them. Segments are swept by their `x` extent so only those
+/// overlapping along `x` are ever compared.
+pub fn crossings(segments: &[Segment]) -> Vec<Crossing> {
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_by_key(|i| segments[*i].x_range().0);
+
+    let mut active: Vec<usize> = Vec::new();
+    let mut output = Vec::new();
+
+    for current in order {
+        let (min_x, _) = segments[current].x_range();
+        active.retain(|i| segments[*i].x_range().1 >= min_x);
+
+        for other in &active {
+            let (i, j) = (current.min(*other), current.max(*other));
+            let (earlier, later) = (&segments[i], &segments[j]);
+
+            let (a, b) = (earlier.y_range(), later.y_range());
+            if a.1 < b.0 || b.1 < a.0 {
+                continue;
+            }
+
+            // A segment's first point belongs to the one before it, so it's
+            // left out unless this is the very first segment.
+            let s_min = if j == 0 { 0 } else { 1 };
+            if let Some((lo, hi)) = meet(earlier, later, s_min) {
+                output.extend((lo..=hi).map(|s| Crossing {
+                    position: later.point(s),
+                    step: later.step + s as usize,
+                    segments: (i, j),
+                }));
+            }
+        }
+        active.push(current);
+    }
+
+    output.sort_by_key(|c| (c.step, c.segments));
+    output.dedup_by_key(|c| c.step);
+    output
+}
+
+pub fn first_crossing(segments: &[Segment]) -> Option<Crossing> {
+    crossings(segments).into_iter().next()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::movement::{parse, Dialect};
+
+    #[test]
+    fn test_revisits() {
+        let moves = parse("^>v<^^", Dialect::Arrows).unwrap();
+        let route = Walker::new(Position::new(0, 0)).route(&moves);
+
+        let expected = vec![
+            Revisit {
+                position: Position::new(0, 0),
+                step: 4,
+                first_step: 0,
+            },
+            Revisit {
+                position: Position::new(1, 0),
+                step: 5,
+                first_step: 1,
+            },
+        ];
+        assert_eq!(expected, revisits(&route));
+        assert_eq!(Some(expected[0]), first_revisit(&route));
+
+        let moves = parse(">>>", Dialect::Arrows).unwrap();
+        let route = Walker::new(Position::new(0, 0)).route(&moves);
+        assert!(revisits(&route).is_empty());
+        assert_eq!(None, first_revisit(&route));
+    }
+
+    #[test]
+    fn test_crossings() {
+        let moves = parse("R8, R4, R4, R8", Dialect::Relative).unwrap();
+        let segs = segments(Position::new(0, 0), &moves);
+        let first = first_crossing(&segs).unwrap();
+
+        assert_eq!(Position::new(0, 4), first.position);
+        assert_eq!(20, first.step);
+        assert_eq!((0, 3), first.segments);
+
+        let route = Walker::new(Position::new(0, 0)).route(&moves);
+        let revisit = first_revisit(&route).unwrap();
+        assert_eq!(first.position, revisit.position);
+        assert_eq!(first.step, revisit.step);
+    }
+
+    #[test]
+    fn test_crossings_match_revisits() {
+        let inputs = [
+            "N5 S3 E2 W4",
+            "NE4 S2 NW2 E3",
+            "E3 N2 W1 S4 E1 N3",
+            "N2 E2 S2 W2 N2",
+            "E5 W3 E6",
+            "NE3 SW3 N1 SE1 E2 W4",
+        ];
+
+        for input in inputs {
+            let moves = parse(input, Dialect::Compass).unwrap();
+            let segs = segments(Position::new(0, 0), &moves);
+            let route = Walker::new(Position::new(0, 0)).route(&moves);
+
+            let expected: Vec<(Position, usize)> = revisits(&route)
+                .iter()
+                .map(|r| (r.position, r.step))
+                .collect();
+            let found: Vec<(Position, usize)> = crossings(&segs)
+                .iter()
+                .map(|c| (c.position, c.step))
+                .collect();
+
+            assert_eq!(expected, found, "{}", input);
+            assert_eq!(
+                first_revisit(&route).map(|r| (r.position, r.step)),
+                first_crossing(&segs).map(|c| (c.position, c.step)),
+                "{}",
+                input
+            );
+        }
+    }
+
+    #[test]
+    fn test_no_crossings() {
+        let moves = parse("N5 E5 S2", Dialect::Compass).unwrap();
+        let segs = segments(Position::new(0, 0), &moves);
+        assert!(crossings(&segs).is_empty());
+        assert_eq!(None, first_crossing(&segs));
+    }
+}
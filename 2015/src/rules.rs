@@ -0,0 +1,295 @@
+use std::fmt::Display;
+use std::fs;
+use std::path::Path;
+
+use crate::naughtynice::{Combinator, Forbid, Repeat, Rule, Vowels};
+
+/// A rule file that couldn't be parsed. `line` starts at 1, 0 meaning the
+/// file couldn't be read at all.
+#[derive(PartialEq, Eq, Debug)]
+pub struct RuleError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl Display for RuleError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+/// Splits `input` on `sep`, ignoring separators inside double quotes.
+fn split_unquoted(input: &str, sep: char) -> Vec<&str> {
+    let mut parts = Vec::new();
+    let mut quoted = false;
+    let mut escaped = false;
+    let mut start = 0;
+
+    for (i, c) in input.char_indices() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if quoted => escaped = true,
+            '"' => quoted = !quoted,
+            c if c == sep && !quoted => {
+                parts.push(&input[start..i]);
+                start = i + c.len_utf8();
+            }
+            _ => {}
+        }
+    }
+    parts.push(&input[start..]);
+    parts
+}
+
+fn parse_number(input: &str) -> Result<usize, String> {
+    input
+        .trim()
+        .parse::<usize>()
+        .map_err(|_| format!("expected a number, got '{}'", input.trim()))
+}
+
+fn parse_quoted(input: &str) -> Result<Vec<u8>, String> {
+    let inner = input
+        .trim()
+        .strip_prefix('"')
+        .and_then(|s| s.strip_suffix('"'))
+        .ok_or(format!("expected a quoted string, got '{}'", input.trim()))?;
+
+    let mut output = Vec::new();
+    let mut chars = inner.chars();
+    while let Some(c) = chars.next() {
+        let c = match c {
+            '\\' => chars.next().ok_or("dangling escape".to_string())?,
+            c => c,
+        };
+        let mut buf = [0; 4];
+        output.extend_from_slice(c.encode_utf8(&mut buf).as_bytes());
+    }
+    Ok(output)
+}
+
+fn parse_vowels(rest: &str) -> Result<Box<dyn Rule>, String> {
+    let rest = rest.trim();
+    let split = rest
+        .find(|c: char| c.is_ascii_digit() || c.is_whitespace())
+        .unwrap_or(rest.len());
+    let (op, n) = rest.split_at(split);
+    let n = parse_number(n)?;
+
+    // Everything boils down to "at least n" rules.
+    let rule: Box<dyn Rule> = match op {
+        ">=" => Box::new(Vowels(n)),
+        ">" => Box::new(Vowels(n + 1)),
+        "<" => Box::new(Combinator::Not(Box::new(Vowels(n)))),
+        "<=" => Box::new(Combinator::Not(Box::new(Vowels(n + 1)))),
+        "==" => Box::new(Combinator::All(vec![
+            Box::new(Vowels(n)),
+            Box::new(Combinator::Not(Box::new(Vowels(n + 1)))),
+        ])),
+        _ => return Err(format!("unknown comparison '{}'", op)),
+    };
+    Ok(rule)
+}
+
+fn parse_gap(width: usize, rest: &str) -> Result<Box<dyn Rule>, String> {
+    let words: Vec<&str> = rest.split_whitespace().collect();
+
+    let gap = match words[..] {
+        [] | ["non-overlapping"] => None,
+        ["gap", gap] => Some(parse_number(gap)?),
+        _ => {
+            return Err(format!(
+                "expected 'gap N' or 'non-overlapping', got '{}'",
+                rest.trim()
+            ))
+        }
+    };
+    Ok(Box::new(Repeat { width, gap }))
+}
+
+fn parse_repeat(rest: &str) -> Result<Box<dyn Rule>, String> {
+    let (width, rest) = rest
+        .strip_prefix('(')
+        .and_then(|s| s.split_once(')'))
+        .ok_or("expected 'repeat(N)'".to_string())?;
+    let width = parse_number(width)?;
+
+    if width == 0 {
+        return Err("repeat width must be at least 1".to_string());
+    }
+    parse_gap(width, rest)
+}
+
+fn parse_forbid(rest: &str) -> Result<Box<dyn Rule>, String> {
+    let words = split_unquoted(rest, ',')
+        .into_iter()
+        .map(parse_quoted)
+        .collect::<Result<Vec<Vec<u8>>, String>>()?;
+
+    if words.iter().any(|w| w.is_empty()) {
+        return Err("forbidden strings can't be empty".to_string());
+    }
+    Ok(Box::new(Forbid(words)))
+}
+
+fn parse_rule(input: &str) -> Result<Box<dyn Rule>, String> {
+    let input = input.trim();
+
+    if let Some(rest) = input.strip_prefix("not ") {
+        return Ok(Box::new(Combinator::Not(parse_rule(rest)?)));
+    }
+
+    let split = input
+        .find(|c: char| c.is_whitespace() || c == '(')
+        .unwrap_or(input.len());
+    let (keyword, rest) = input.split_at(split);
+
+    match keyword {
+        "vowels" => parse_vowels(rest),
+        "repeat" => parse_repeat(rest),
+        "pair-twice" => parse_gap(2, rest),
+        "gap-repeat" => Ok(Box::new(Repeat {
+            width: 1,
+            gap: Some(parse_number(rest)?),
+        })),
+        "forbid" => parse_forbid(rest),
+        "" => Err("missing rule".to_string()),
+        _ => Err(format!("unknown rule '{}'", keyword)),
+    }
+}
+
+/// Parses a rule file. Every line holds one rule and a string has to pass
+/// all of them to be nice. Alternatives on the same line are separated by
+/// `|`, a rule is negated with a leading `not`, and `#` starts a comment.
+///
+/// ```text
+/// vowels >= 3
+/// repeat(1) gap 0
+/// forbid "ab","cd","pq","xy"
+/// pair-twice non-overlapping | gap-repeat 1
+/// ```
+pub fn parse_rules(input: &str) -> Result<Combinator, RuleError> {
+    let mut rules: Vec<Box<dyn Rule>> = Vec::new();
+
+    for (i, line) in input.lines().enumerate() {
+        let line = split_unquoted(line, '#')[0].trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        let error = |message| RuleError {
+            line: i + 1,
+            message,
+        };
+
+        let mut alternatives = split_unquoted(line, '|')
+            .into_iter()
+            .map(parse_rule)
+            .collect::<Result<Vec<Box<dyn Rule>>, String>>()
+            .map_err(error)?;
+
+        if alternatives.len() == 1 {
+            rules.push(alternatives.pop().unwrap());
+        } else {
+            rules.push(Box::new(Combinator::Any(alternatives)));
+        }
+    }
+    Ok(Combinator::All(rules))
+}
+
+pub fn load_rules(path: impl AsRef<Path>) -> Result<Combinator, RuleError> {
+    let input = fs::read_to_string(path).map_err(|e| RuleError {
+        line: 0,
+        message: e.to_string(),
+    })?;
+
+    parse_rules(&input)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::naughtynice::{check, validate, RuleSet, Value};
+
+    const INPUTS: [&str; 9] = [
+        "ugknbfddgicrmopn",
+        "aaa",
+        "jchzalrnumimnmhp",
+        "haegwjzuvuyypxyu",
+        "dvszwmarrgswjxmb",
+        "qjhvhtzxzqqjkmpb",
+        "xxyxx",
+        "uurcxstgmygtbstg",
+        "ieodomkazucvgmuy",
+    ];
+
+    #[test]
+    fn test_rule_sets() {
+        let original = parse_rules(
+            "# Original rules\nvowels >= 3\nrepeat(1) gap 0\nforbid \"ab\",\"cd\",\"pq\",\"xy\"\n",
+        )
+        .unwrap();
+        let revised = parse_rules("pair-twice non-overlapping\n\ngap-repeat 1 # xyx\n").unwrap();
+
+        for input in INPUTS {
+            assert_eq!(
+                validate(input, RuleSet::Original),
+                check(input.as_bytes(), &original).value
+            );
+            assert_eq!(
+                validate(input, RuleSet::Revised),
+                check(input.as_bytes(), &revised).value
+            );
+        }
+    }
+
+    #[test]
+    fn test_syntax() {
+        let rules = parse_rules("not vowels > 1 | forbid \"|\", \"#\"\nrepeat(2) gap 1").unwrap();
+
+        assert_eq!(Value::Nice, check(b"xyzxy", &rules).value);
+        assert_eq!(Value::Naughty, check(b"aeiyzaei", &rules).value);
+        assert_eq!(Value::Nice, check(b"aeiaeixy", &rules).value);
+        assert_eq!(Value::Naughty, check(b"a|xab#b", &rules).value);
+
+        let rules = parse_rules("vowels == 2").unwrap();
+        assert_eq!(Value::Naughty, check(b"bab", &rules).value);
+        assert_eq!(Value::Nice, check(b"baeb", &rules).value);
+        assert_eq!(Value::Naughty, check(b"baeib", &rules).value);
+    }
+
+    #[test]
+    fn test_errors() {
+        let cases = [
+            ("vowels >= 3\nsparkles", 2, "unknown rule 'sparkles'"),
+            ("vowels ~ 3", 1, "unknown comparison '~'"),
+            ("\n\nrepeat(x) gap 0", 3, "expected a number, got 'x'"),
+            (
+                "repeat(1) every 2",
+                1,
+                "expected 'gap N' or 'non-overlapping', got 'every 2'",
+            ),
+            ("forbid ab", 1, "expected a quoted string, got 'ab'"),
+            ("vowels >= 1 |", 1, "missing rule"),
+        ];
+
+        for (input, line, message) in cases {
+            let error = parse_rules(input).err().unwrap();
+            assert_eq!(
+                RuleError {
+                    line,
+                    message: message.to_string()
+                },
+                error
+            );
+        }
+        assert_eq!(0, load_rules("data/missing.rules").err().unwrap().line);
+        assert_eq!(
+            "line 2: unknown rule 'sparkles'",
+            parse_rules("vowels >= 3\nsparkles")
+                .err()
+                .unwrap()
+                .to_string()
+        );
+    }
+}
@@ -0,0 +1,476 @@
+use std::any::Any;
+use std::collections::{HashMap, VecDeque};
+use std::io::{self, BufRead};
+use std::thread;
+
+use crate::naughtynice::{Combinator, Forbid, Repeat, Rule, Vowels, VOWELS};
+
+/// Lines read ahead per thread before handing them out.
+const BATCH: usize = 4096;
+
+/// Boolean structure of a lowered rule, leaves pointing at matchers.
+/// Rules the scanner doesn't know about are evaluated on the whole line.
+enum Expr<'r> {
+    Leaf(usize),
+    Rule(&'r dyn Rule),
+    All(Vec<Expr<'r>>),
+    Any(Vec<Expr<'r>>),
+    Not(Box<Expr<'r>>),
+}
+
+/// Dense Aho–Corasick automaton: every state has a transition for every
+/// byte, so feeding a byte is a single lookup.
+struct Automaton {
+    next: Vec<[u32; 256]>,
+    hit: Vec<bool>,
+}
+
+impl Automaton {
+    fn new(words: &[Vec<u8>]) -> Automaton {
+        const NONE: u32 = u32::MAX;
+
+        let mut next = vec![[NONE; 256]];
+        let mut hit = vec![false];
+
+        for word in words {
+            let mut state = 0;
+            for b in word {
+                if next[state][*b as usize] == NONE {
+                    next[state][*b as usize] = next.len() as u32;
+                    next.push([NONE; 256]);
+                    hit.push(false);
+                }
+                state = next[state][*b as usize] as usize;
+            }
+            hit[state] = true;
+        }
+
+        // Breadth first so a state's failure link is complete before any of
+        // its children need it.
+        let mut fail = vec![0; next.len()];
+        let mut queue = VecDeque::new();
+
+        for slot in next[0].iter_mut() {
+            match *slot {
+                NONE => *slot = 0,
+                child => queue.push_back(child as usize),
+            }
+        }
+
+        while let Some(state) = queue.pop_front() {
+            let fallbacks = next[fail[state]];
+
+            for (slot, fallback) in next[state].iter_mut().zip(fallbacks) {
+                match *slot {
+                    NONE => *slot = fallback,
+                    child => {
+                        let child = child as usize;
+                        fail[child] = fallback as usize;
+                        hit[child] |= hit[fallback as usize];
+                        queue.push_back(child);
+                    }
+                }
+            }
+        }
+
+        Automaton { next, hit }
+    }
+}
+
+/// Single rule evaluated one byte at a time.
+enum Matcher {
+    /// At least `min` bytes out of a set, one bit per byte value.
+    Count { set: [u64; 4], min: usize },
+    /// `width` bytes repeated exactly `gap` bytes later.
+    Window { width: usize, gap: usize },
+    /// `width` bytes repeated anywhere later without overlapping. Up to two
+    /// bytes wide this is a fixed table, wider windows are kept in a map
+    /// that grows with the number of distinct windows on the line.
+    Seen { width: usize },
+    /// Passes when none of the automaton's words show up.
+    Absent(Automaton),
+}
+
+/// Per line state of a matcher, reused from one line to the next.
+enum State {
+    Count(usize),
+    Window {
+        recent: VecDeque<u8>,
+        matched: bool,
+    },
+    /// First start seen for every window of at most two bytes, tagged with
+    /// the line it was seen on so nothing needs clearing between lines.
+    Table {
+        first: Vec<(usize, usize)>,
+        line: usize,
+        matched: bool,
+    },
+    Map {
+        first: HashMap<Vec<u8>, usize>,
+        matched: bool,
+    },
+    Automaton {
+        state: usize,
+        hit: bool,
+    },
+}
+
+impl Matcher {
+    fn state(&self) -> State {
+        match self {
+            Matcher::Count { .. } => State::Count(0),
+            Matcher::Window { width, gap } => State::Window {
+                recent: VecDeque::with_capacity(2 * width + gap),
+                matched: false,
+            },
+            Matcher::Seen { width } if *width <= 2 => State::Table {
+                first: vec![(0, 0); 1 << (8 * width)],
+                line: 0,
+                matched: false,
+            },
+            Matcher::Seen { .. } => State::Map {
+                first: HashMap::new(),
+                matched: false,
+            },
+            Matcher::Absent(_) => State::Automaton {
+                state: 0,
+                hit: false,
+            },
+        }
+    }
+
+    fn reset(&self, state: &mut State) {
+        match state {
+            State::Count(n) => *n = 0,
+            State::Window { recent, matched } => {
+                recent.clear();
+                *matched = false;
+            }
+            State::Table { line, matched, .. } => {
+                *line += 1;
+                *matched = false;
+            }
+            State::Map { first, matched } => {
+                first.clear();
+                *matched = false;
+            }
+            State::Automaton { state, hit } => {
+                *state = 0;
+                *hit = false;
+            }
+        }
+    }
+
+    /// Feeds the byte at `pos`, `line` holding every byte up to it.
+    fn feed(&self, state: &mut State, line: &[u8], pos: usize) {
+        let b = line[pos];
+
+        match (self, state) {
+            (Matcher::Count { set, .. }, State::Count(n)) => {
+                if set[b as usize / 64] >> (b % 64) & 1 == 1 {
+                    *n += 1;
+                }
+            }
+            (Matcher::Window { width, gap }, State::Window { recent, matched }) => {
+                let len = 2 * width + gap;
+                if recent.len() == len {
+                    recent.pop_front();
+                }
+                recent.push_back(b);
+
+                if recent.len() == len && (0..*width).all(|i| recent[i] == recent[width + gap + i])
+                {
+                    *matched = true;
+                }
+            }
+            (
+                Matcher::Seen { width },
+                State::Table {
+                    first,
+                    line: id,
+                    matched,
+                },
+            ) => {
+                if pos + 1 < *width {
+                    return;
+                }
+
+                let start = pos + 1 - width;
+                let key = line[start..=pos]
+                    .iter()
+                    .fold(0, |acc, b| (acc << 8) | *b as usize);
+
+                match first[key] {
+                    (l, f) if l == *id => *matched |= f + width <= start,
+                    _ => first[key] = (*id, start),
+                }
+            }
+            (Matcher::Seen { width }, State::Map { first, matched }) => {
+                if pos + 1 < *width {
+                    return;
+                }
+
+                let start = pos + 1 - width;
+                let f = *first.entry(line[start..=pos].to_vec()).or_insert(start);
+                *matched |= f + width <= start;
+            }
+            (Matcher::Absent(automaton), State::Automaton { state, hit }) => {
+                *state = automaton.next[*state][b as usize] as usize;
+                *hit |= automaton.hit[*state];
+            }
+            _ => unreachable!("matcher fed with the wrong state"),
+        }
+    }
+
+    fn passed(&self, state: &State) -> bool {
+        match (self, state) {
+            (Matcher::Count { min, .. }, State::Count(n)) => n >= min,
+            (_, State::Window { matched, .. }) => *matched,
+            (_, State::Table { matched, .. }) => *matched,
+            (_, State::Map { matched, .. }) => *matched,
+            (_, State::Automaton { hit, .. }) => !hit,
+            _ => unreachable!("matcher checked with the wrong state"),
+        }
+    }
+}
+
+#[derive(PartialEq, Eq, Debug, Default, Clone, Copy)]
+pub struct Tally {
+    pub nice: usize,
+    pub naughty: usize,
+}
+
+/// Registers a matcher, returning the leaf referring to it.
+fn add<'r>(matchers: &mut Vec<Matcher>, matcher: Matcher) -> Expr<'r> {
+    matchers.push(matcher);
+    Expr::Leaf(matchers.len() - 1)
+}
+
+/// Turns the built-in rules making up `rule` into matchers. Anything else is
+/// left to its own `evaluate`.
+fn lower<'r>(rule: &'r dyn Rule, matchers: &mut Vec<Matcher>) -> Expr<'r> {
+    let any: &dyn Any = rule;
+
+    if let Some(Vowels(min)) = any.downcast_ref() {
+        let mut set = [0; 4];
+        for v in VOWELS {
+            set[*v as usize / 64] |= 1 << (v % 64);
+        }
+        return add(matchers, Matcher::Count { set, min: *min });
+    }
+
+    if let Some(Repeat { width, gap }) = any.downcast_ref() {
+        let width = (*width).max(1);
+        let matcher = match gap {
+            Some(gap) => Matcher::Window { width, gap: *gap },
+            None => Matcher::Seen { width },
+        };
+        return add(matchers, matcher);
+    }
+
+    if let Some(Forbid(words)) = any.downcast_ref() {
+        return add(matchers, Matcher::Absent(Automaton::new(words)));
+    }
+
+    match any.downcast_ref() {
+        Some(Combinator::All(rules)) => Expr::All(lower_each(rules, matchers)),
+        Some(Combinator::Any(rules)) => Expr::Any(lower_each(rules, matchers)),
+        Some(Combinator::Not(rule)) => Expr::Not(Box::new(lower(rule.as_ref(), matchers))),
+        None => Expr::Rule(rule),
+    }
+}
+
+fn lower_each<'r>(rules: &'r [Box<dyn Rule>], matchers: &mut Vec<Matcher>) -> Vec<Expr<'r>> {
+    rules.iter().map(|r| lower(r.as_ref(), matchers)).collect()
+}
+
+/// Evaluates the built-in rules in one pass over each string, keeping a
+/// constant amount of state no matter how long the string is, apart from
+/// repeats wider than two bytes (see `Matcher::Seen`). Other rules are
+/// handed the whole string.
+pub struct Scanner<'r> {
+    matchers: Vec<Matcher>,
+    expr: Expr<'r>,
+}
+
+impl<'r> Scanner<'r> {
+    pub fn new(rule: &'r dyn Rule) -> Scanner<'r> {
+        let mut matchers = Vec::new();
+        let expr = lower(rule, &mut matchers);
+
+        Scanner { matchers, expr }
+    }
+
+    fn eval(&self, expr: &Expr, states: &[State], line: &[u8]) -> bool {
+        match expr {
+            Expr::Leaf(i) => self.matchers[*i].passed(&states[*i]),
+            Expr::Rule(rule) => rule.evaluate(line, &mut Vec::new()),
+            Expr::All(exprs) => exprs.iter().all(|e| self.eval(e, states, line)),
+            Expr::Any(exprs) => exprs.iter().any(|e| self.eval(e, states, line)),
+            Expr::Not(expr) => !self.eval(expr, states, line),
+        }
+    }
+
+    fn is_nice_with(&self, line: &[u8], states: &mut [State]) -> bool {
+        for (matcher, state) in self.matchers.iter().zip(states.iter_mut()) {
+            matcher.reset(state);
+        }
+
+        for pos in 0..line.len() {
+            for (matcher, state) in self.matchers.iter().zip(states.iter_mut()) {
+                matcher.feed(state, line, pos);
+            }
+        }
+
+        self.eval(&self.expr, states, line)
+    }
+
+    fn states(&self) -> Vec<State> {
+        self.matchers.iter().map(|m| m.state()).collect()
+    }
+
+    pub fn is_nice(&self, line: &[u8]) -> bool {
+        self.is_nice_with(line, &mut self.states())
+    }
+
+    fn tally(&self, lines: &[String]) -> Tally {
+        let mut states = self.states();
+        let mut tally = Tally::default();
+
+        for line in lines {
+            if self.is_nice_with(line.as_bytes(), &mut states) {
+                tally.nice += 1;
+            } else {
+                tally.naughty += 1;
+            }
+        }
+        tally
+    }
+
+    /// Classifies every line of `reader`, spreading batches of lines over
+    /// `threads` threads.
+    pub fn scan(&self, reader: impl BufRead, threads: usize) -> io::Result<Tally> {
+        let threads = threads.max(1);
+        let mut lines = reader.lines();
+        let mut tally = Tally::default();
+
+        loop {
+            let batch = lines
+                .by_ref()
+                .take(BATCH * threads)
+                .collect::<io::Result<Vec<String>>>()?;
+            if batch.is_empty() {
+                return Ok(tally);
+            }
+
+            let size = batch.len().div_ceil(threads);
+            let results: Vec<Tally> = thread::scope(|s| {
+                let handles: Vec<_> = batch
+                    .chunks(size)
+                    .map(|chunk| s.spawn(|| self.tally(chunk)))
+                    .collect();
+
+                handles.into_iter().map(|h| h.join().unwrap()).collect()
+            });
+
+            for t in results {
+                tally.nice += t.nice;
+                tally.naughty += t.naughty;
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::naughtynice::{check, Outcome, RuleSet, Value};
+    use crate::rules::parse_rules;
+    use std::fs;
+    use std::io::BufReader;
+
+    /// Rule the scanner has no matcher for.
+    struct Palindrome;
+
+    impl Rule for Palindrome {
+        fn name(&self) -> String {
+            "palindrome".to_string()
+        }
+
+        fn evaluate(&self, input: &[u8], outcomes: &mut Vec<Outcome>) -> bool {
+            let passed = input.iter().eq(input.iter().rev());
+            outcomes.push(Outcome {
+                rule: self.name(),
+                passed,
+                indices: Vec::new(),
+            });
+            passed
+        }
+    }
+
+    #[test]
+    fn test_automaton() {
+        let rule = Forbid(vec![
+            b"he".to_vec(),
+            b"she".to_vec(),
+            b"hers".to_vec(),
+            b"is".to_vec(),
+        ]);
+        let scanner = Scanner::new(&rule);
+
+        assert!(!scanner.is_nice(b"ushers"));
+        assert!(!scanner.is_nice(b"this"));
+        assert!(!scanner.is_nice(b"shhe"));
+        assert!(scanner.is_nice(b"hrsh"));
+        assert!(scanner.is_nice(b""));
+    }
+
+    #[test]
+    fn test_matches_check() {
+        let input = fs::read_to_string("data/day5.txt").unwrap();
+        let rules: Vec<Box<dyn Rule>> = vec![
+            RuleSet::Original.rule(),
+            RuleSet::Revised.rule(),
+            Box::new(
+                parse_rules(
+                    "vowels == 4 | not repeat(3) non-overlapping\nforbid \"aa\",\"zz\" | gap-repeat 2",
+                )
+                .unwrap(),
+            ),
+            Box::new(Combinator::Any(vec![
+                Box::new(Palindrome),
+                Box::new(Vowels(3)),
+            ])),
+            Box::new(Combinator::All(vec![
+                Box::new(Combinator::Not(Box::new(Palindrome))),
+                RuleSet::Revised.rule(),
+            ])),
+        ];
+
+        for rule in rules {
+            let scanner = Scanner::new(rule.as_ref());
+            for line in input.lines().chain(["", "a", "aaaa", "abab", "xyzzyx"]) {
+                let expected = check(line.as_bytes(), rule.as_ref()).value == Value::Nice;
+                assert_eq!(expected, scanner.is_nice(line.as_bytes()), "{}", line);
+            }
+        }
+    }
+
+    #[test]
+    fn test_scan() {
+        let input = fs::read_to_string("data/day5.txt").unwrap();
+
+        for (rules, nice) in [(RuleSet::Original, 258), (RuleSet::Revised, 53)] {
+            let rule = rules.rule();
+            let scanner = Scanner::new(rule.as_ref());
+
+            for threads in [1, 4] {
+                let tally = scanner
+                    .scan(BufReader::new(input.as_bytes()), threads)
+                    .unwrap();
+                assert_eq!(nice, tally.nice);
+                assert_eq!(1000 - nice, tally.naughty);
+            }
+        }
+    }
+}